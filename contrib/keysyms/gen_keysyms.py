@@ -0,0 +1,107 @@
+#!/usr/bin/env python3
+# Generates src/keysyms.rs, the keysym name to evdev keycode table used by the config parser.
+#
+# The table is built from the same XKB data the X server and Wayland compositors use for a
+# default `pc105` keyboard with the `us` layout (`pc+us+inet(evdev)`), so every keysym that can
+# be bound in sxhkd on such a keyboard can be bound in swhkd too.
+#
+# Usage: ./contrib/keysyms/gen_keysyms.py [/usr/share/X11/xkb] > src/keysyms.rs
+
+import os
+import re
+import sys
+
+XKB_ROOT = sys.argv[1] if len(sys.argv) > 1 else "/usr/share/X11/xkb"
+SYMBOLS = ["pc", "us", "inet(evdev)"]
+
+# XKB keycodes are evdev keycodes shifted by 8
+EVDEV_OFFSET = 8
+
+
+def strip_comments(text):
+    text = re.sub(r"/\*.*?\*/", "", text, flags=re.S)
+    return re.sub(r"(//|#).*", "", text)
+
+
+def find_section(kind, name):
+    file_name, _, section = name.partition("(")
+    section = section.rstrip(")")
+    with open(os.path.join(XKB_ROOT, kind, file_name)) as f:
+        text = strip_comments(f.read())
+
+    # Sections are `[flags] xkb_<kind> "name" { ... };`, the default one is flagged `default`
+    pattern = re.compile(r'((?:\w+\s+)*)xkb_\w+\s+"([^"]+)"\s*{', re.S)
+    for match in pattern.finditer(text):
+        flags, section_name = match.group(1).split(), match.group(2)
+        if section_name == section or (not section and "default" in flags):
+            depth, start = 1, match.end()
+            for i in range(start, len(text)):
+                depth += {"{": 1, "}": -1}.get(text[i], 0)
+                if depth == 0:
+                    return text[start:i]
+    raise KeyError("No section {} in {}/{}".format(name, kind, file_name))
+
+
+def load_keycodes(name, keycodes):
+    body = find_section("keycodes", name)
+    for include in re.findall(r'include\s+"([^"]+)"', body):
+        load_keycodes(include, keycodes)
+    for key, code in re.findall(r"<([^>]+)>\s*=\s*(\d+)\s*;", body):
+        keycodes[key] = int(code) - EVDEV_OFFSET
+    for alias, key in re.findall(r"alias\s+<([^>]+)>\s*=\s*<([^>]+)>\s*;", body):
+        if key in keycodes:
+            keycodes[alias] = keycodes[key]
+
+
+def load_symbols(name, symbols):
+    body = find_section("symbols", name)
+    statements = re.finditer(
+        r'include\s+"([^"]+)"|(?:\w+\s+)?key\s+<([^>]+)>\s*{(.*?)}\s*;', body, flags=re.S
+    )
+    for statement in statements:
+        include, key, definition = statement.groups()
+        if include:
+            for part in include.split("+"):
+                load_symbols(part, symbols)
+            continue
+        # Either `[ a, A ]` or `symbols[Group1] = [ a, A ]`, only the first group matters
+        levels = re.search(r"\[([^\]]*)\]\s*(?:,|$)", definition.replace("symbols[Group1]", ""))
+        if levels:
+            symbols[key] = [level.strip() for level in levels.group(1).split(",")]
+
+
+def main():
+    keycodes = {}
+    load_keycodes("evdev", keycodes)
+    symbols = {}
+    for name in SYMBOLS:
+        load_symbols(name, symbols)
+
+    # Prefer unshifted keysyms, so `1` and `exclam` both resolve to KEY_1. A keysym found on
+    # several keys resolves to the lowest keycode, except that the F13-F24 keys are only used
+    # as a fallback, since inet(evdev) maps them to media keysyms for a few vendor keyboards.
+    def key_order(item):
+        is_f13_to_f24 = re.match(r"FK(1[3-9]|2[0-4])$", item[0]) is not None
+        return (is_f13_to_f24, keycodes.get(item[0], 0))
+
+    table = {}
+    max_level = max(len(levels) for levels in symbols.values())
+    for level in range(max_level):
+        for key, levels in sorted(symbols.items(), key=key_order):
+            if key not in keycodes or level >= len(levels):
+                continue
+            keysym = levels[level].lower()
+            if keysym in ("", "nosymbol", "voidsymbol") or keysym in table:
+                continue
+            table[keysym] = keycodes[key]
+
+    print("// Generated by contrib/keysyms/gen_keysyms.py, do not edit.")
+    print("// Keysym names are lowercase because the config parser is case insensitive.")
+    print("pub const KEYSYMS: &[(&str, u16)] = &[")
+    for keysym, code in sorted(table.items(), key=lambda item: (item[1], item[0])):
+        print('    ("{}", {}),'.format(keysym, code))
+    print("];")
+
+
+if __name__ == "__main__":
+    main()
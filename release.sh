@@ -3,10 +3,11 @@ rm Cargo.lock  # Ensures that features and dependencies are fine when we cargo u
 version=$(awk -F = '/^version/ {print $2}' Cargo.toml | awk '{$1=$1;print}' | tr -d '"')
 make
 cd bin
-zip -r "musl_libc-x86_64-$version.zip" swhkd swhks
+zip -r "musl_libc-x86_64-$version.zip" swhkd swhks swhkctl
 cd ..
 make glibc
 cd bin
-zip -r "glibc-x86_64-$version.zip" swhkd swhks
+zip -r "glibc-x86_64-$version.zip" swhkd swhks swhkctl
 rm ./swhkd 
 rm ./swhks
+rm ./swhkctl
@@ -1,10 +1,15 @@
+use crate::devices::{DeviceFilter, DeviceInfo, Matcher, Seat};
+use crate::keysyms::KEYSYMS;
+use crate::layout::{keysym_to_char, Layout};
 use itertools::Itertools;
+use nix::unistd::{geteuid, User};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 // use std::str::pattern::Pattern;
 use std::{
     fmt,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
@@ -12,15 +17,315 @@ use std::{
 pub enum Error {
     ConfigNotFound,
     Io(std::io::Error),
-    InvalidConfig(ParseError),
+    // Every error found in the config, in file and line order
+    InvalidConfig(Vec<Diagnostic>),
+    InvalidLayout(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum ParseError {
     // u32 is the line number where an error occured
     UnknownSymbol(PathBuf, u32),
     InvalidModifier(PathBuf, u32),
     InvalidKeysym(PathBuf, u32),
+    // The path and line of the `include` statement
+    MissingInclude(PathBuf, u32),
+    UnreadableInclude(PathBuf, u32, std::io::ErrorKind),
+    UndefinedVariable(PathBuf, u32),
+    UnterminatedQuote(PathBuf, u32),
+}
+
+impl ParseError {
+    pub fn path(&self) -> &Path {
+        match self {
+            ParseError::UnknownSymbol(path, _)
+            | ParseError::InvalidModifier(path, _)
+            | ParseError::InvalidKeysym(path, _)
+            | ParseError::MissingInclude(path, _)
+            | ParseError::UnreadableInclude(path, _, _)
+            | ParseError::UndefinedVariable(path, _)
+            | ParseError::UnterminatedQuote(path, _) => path,
+        }
+    }
+
+    pub fn line_nr(&self) -> u32 {
+        match self {
+            ParseError::UnknownSymbol(_, line_nr)
+            | ParseError::InvalidModifier(_, line_nr)
+            | ParseError::InvalidKeysym(_, line_nr)
+            | ParseError::MissingInclude(_, line_nr)
+            | ParseError::UnreadableInclude(_, line_nr, _)
+            | ParseError::UndefinedVariable(_, line_nr)
+            | ParseError::UnterminatedQuote(_, line_nr) => *line_nr,
+        }
+    }
+}
+
+// A parse error along with the token it is about and where that token is in its line, so it can
+// be shown the way rustc shows errors
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub token: String,
+    // Until the diagnostic is located, the keybinding the token was parsed from if any
+    pub source_line: String,
+    // Character columns of the token in the source line, starting at 0
+    pub columns: Range<usize>,
+    pub suggestion: Option<String>,
+}
+
+// Suggestions are only made for names this close to the unknown one
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+impl Diagnostic {
+    pub fn new(error: ParseError, token: &str) -> Self {
+        Diagnostic {
+            error,
+            token: token.trim().to_string(),
+            source_line: String::new(),
+            columns: 0..0,
+            suggestion: None,
+        }
+    }
+
+    fn in_keybinding(mut self, keybinding: &str, columns: Range<usize>) -> Self {
+        self.source_line = keybinding.to_string();
+        self.columns = columns;
+        self
+    }
+
+    // Finds the token in the config it was parsed from, and for unknown symbols the known name
+    // it most likely stands for
+    fn locate<'a>(mut self, contents: &str, names: impl Iterator<Item = &'a str>) -> Self {
+        let line_index = (self.error.line_nr() as usize).saturating_sub(1);
+        let line = contents.split('\n').nth(line_index).unwrap_or("").trim_end_matches('\r');
+        let keybinding = std::mem::replace(&mut self.source_line, line.to_string());
+
+        // The keybinding is placed in the line as a whole, so the token is not mistaken for a part
+        // of another one. One rewritten by curly braces isn't in the line as is, and is searched
+        // for by its token instead.
+        let in_keybinding = match keybinding.as_str() {
+            "" => None,
+            keybinding => find_keybinding(line, keybinding),
+        };
+        self.columns = match in_keybinding {
+            Some(start) => start + self.columns.start..start + self.columns.end,
+            None => self.find_token(line),
+        };
+
+        if let ParseError::UnknownSymbol(..) = self.error {
+            let token = self.token.to_lowercase();
+            self.suggestion = names
+                .map(|name| (edit_distance(&token, name), name))
+                .filter(|(distance, _)| {
+                    *distance <= MAX_SUGGESTION_DISTANCE && *distance < token.chars().count()
+                })
+                .min()
+                .map(|(_, name)| name.to_string());
+        }
+        self
+    }
+
+    // Where the token is first written in the line
+    fn find_token(&self, line: &str) -> Range<usize> {
+        let lowercase_line = line.to_lowercase();
+        let found = if self.token.is_empty() {
+            None
+        } else if lowercase_line.len() == line.len() {
+            lowercase_line.find(&self.token.to_lowercase())
+        } else {
+            line.find(&self.token)
+        };
+        match found {
+            Some(start) => {
+                let start = line[..start].chars().count();
+                start..start + self.token.chars().count()
+            }
+            // A missing token is pointed at the end of the line, and a token that is not on this
+            // line, like in a line continued with `\`, at the whole line
+            None if self.token.is_empty() => {
+                let end = line.trim_end().chars().count();
+                end..end + 1
+            }
+            None => {
+                let start = line.chars().take_while(|c| c.is_whitespace()).count();
+                start..line.trim_end().chars().count()
+            }
+        }
+    }
+}
+
+// Character column where the keybinding starts in the line, only counting whole words so `a`
+// isn't found in `alt`
+fn find_keybinding(line: &str, keybinding: &str) -> Option<usize> {
+    let lowercase_line = line.to_lowercase();
+    let (line, keybinding) = if lowercase_line.len() == line.len() {
+        (lowercase_line, keybinding.to_lowercase())
+    } else {
+        (line.to_string(), keybinding.to_string())
+    };
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(&keybinding)
+        .map(|(start, _)| start)
+        .find(|&start| {
+            !line[..start].ends_with(is_word)
+                && !line[start + keybinding.len()..].starts_with(is_word)
+        })
+        .map(|start| line[..start].chars().count())
+}
+
+// A binding that loads but can not work as written, like a duplicate that is never used
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    pub path: PathBuf,
+    // None for warnings about how bindings interact rather than about one line
+    pub line_nr: Option<u32>,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(path: PathBuf, line_nr: Option<u32>, message: String) -> Self {
+        Warning { path, line_nr, message }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line_nr {
+            Some(line_nr) => {
+                let gutter = " ".repeat(line_nr.to_string().len());
+                write!(
+                    f,
+                    "warning: {}\n{}--> {}:{}",
+                    self.message,
+                    gutter,
+                    self.path.display(),
+                    line_nr
+                )
+            }
+            None => write!(f, "warning: {}\n --> {}", self.message, self.path.display()),
+        }
+    }
+}
+
+// A keybinding as it appears in warnings, with its mode
+fn describe_binding(keybinding: &KeyBinding, mode: Option<&str>) -> String {
+    match mode {
+        Some(mode) => format!("`{}` in mode {}", keybinding, mode),
+        None => format!("`{}`", keybinding),
+    }
+}
+
+// Hotkeys that can never trigger, or that fight over a key, once every file is merged
+fn lint_bindings(bindings: &Bindings, path: &Path) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    for (i, hotkey) in bindings.hotkeys.iter().enumerate() {
+        let mut warn = |message: String| warnings.push(hotkey.warning(path, message));
+
+        // `~key` lets the key through, but `key` swallows it
+        let collision = bindings.hotkeys[..i].iter().find(|other| {
+            other.keybinding.send != hotkey.keybinding.send
+                && other.keybinding.on_release == hotkey.keybinding.on_release
+                && KeyBinding::new(other.keysym(), other.modifiers())
+                    == KeyBinding::new(hotkey.keysym(), hotkey.modifiers())
+                && other.mode == hotkey.mode
+                && other.devices == hotkey.devices
+        });
+        if let Some(other) = collision {
+            warn(format!(
+                "{} and {}{} are both bound, so whether the key is passed through depends on their order",
+                describe_binding(&hotkey.keybinding, hotkey.mode.as_deref()),
+                describe_binding(&other.keybinding, other.mode.as_deref()),
+                other.describe_origin(),
+            ));
+        }
+
+        let remap = bindings.remaps.iter().find(|remap| {
+            remap.from.keysym == hotkey.keysym()
+                && remap.from.modifiers.iter().all(|modifier| hotkey.modifiers().contains(modifier))
+        });
+        if let Some(remap) = remap {
+            warn(format!(
+                "{} can never trigger, `{}` is remapped to `{}`",
+                describe_binding(&hotkey.keybinding, hotkey.mode.as_deref()),
+                remap.from,
+                remap.to
+            ));
+        }
+
+        if bindings.tap_holds.iter().any(|tap_hold| tap_hold.key == hotkey.keysym()) {
+            warn(format!(
+                "{} can never trigger, its key is a tap-hold key",
+                describe_binding(&hotkey.keybinding, hotkey.mode.as_deref()),
+            ));
+        }
+    }
+    warnings
+}
+
+// Levenshtein distance, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownSymbol(..) => "unknown symbol".fmt(f),
+            ParseError::InvalidModifier(..) => "invalid modifier".fmt(f),
+            ParseError::InvalidKeysym(..) => "invalid keysym".fmt(f),
+            ParseError::MissingInclude(..) => "included file not found".fmt(f),
+            ParseError::UnreadableInclude(_, _, kind) => {
+                write!(f, "unable to read included file ({})", std::io::Error::from(*kind))
+            }
+            ParseError::UndefinedVariable(..) => "undefined variable".fmt(f),
+            ParseError::UnterminatedQuote(..) => "unterminated quote".fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_nr = self.error.line_nr().to_string();
+        let gutter = " ".repeat(line_nr.len());
+        if self.token.is_empty() {
+            writeln!(f, "error: {}", self.error)?;
+        } else {
+            writeln!(f, "error: {} `{}`", self.error, self.token)?;
+        }
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter,
+            self.error.path().display(),
+            line_nr,
+            self.columns.start + 1
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_nr, self.source_line)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.columns.start),
+            "^".repeat(self.columns.len().max(1))
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n{} = help: did you mean `{}`?", gutter, suggestion)?;
+        }
+        Ok(())
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -39,34 +344,102 @@ impl fmt::Display for Error {
             Error::ConfigNotFound => "Config file not found.".fmt(f),
 
             Error::Io(io_err) => format!("I/O Error while parsing config file: {}", io_err).fmt(f),
-            Error::InvalidConfig(parse_err) => match parse_err {
-                ParseError::UnknownSymbol(path, line_nr) => format!(
-                    "Error parsing config file {:?}. Unknown symbol at line {}.",
-                    path, line_nr
-                )
-                .fmt(f),
-                ParseError::InvalidKeysym(path, line_nr) => format!(
-                    "Error parsing config file {:?}. Invalid keysym at line {}.",
-                    path, line_nr
-                )
-                .fmt(f),
-                ParseError::InvalidModifier(path, line_nr) => format!(
-                    "Error parsing config file {:?}. Invalid modifier at line {}.",
-                    path, line_nr
-                )
-                .fmt(f),
-            },
+            Error::InvalidConfig(diagnostics) => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "{}\n", diagnostic)?;
+                }
+                match diagnostics.len() {
+                    1 => "Found 1 error in the config.".fmt(f),
+                    count => format!("Found {} errors in the config.", count).fmt(f),
+                }
+            }
+            Error::InvalidLayout(err) => format!("Unable to load keyboard layout: {}", err).fmt(f),
         }
     }
 }
 
 pub const IMPORT_STATEMENT: &str = "include";
+pub const LAYOUT_STATEMENT: &str = "layout";
+pub const PRECEDENCE_STATEMENT: &str = "precedence";
+pub const OVERRIDE_STATEMENT: &str = "override";
+pub const UNBIND_STATEMENT: &str = "unbind";
+pub const ALLOW_DEVICE_STATEMENT: &str = "allow-device";
+pub const DENY_DEVICE_STATEMENT: &str = "deny-device";
+pub const DEVICE_SECTION: &str = "device";
+pub const SEAT_STATEMENT: &str = "seat";
+pub const REMAP_STATEMENT: &str = "remap";
+pub const REMAP_ARROW: &str = "->";
+pub const TAPHOLD_STATEMENT: &str = "taphold";
+pub const TAPHOLD_PERMISSIVE_HOLD_FLAG: &str = "permissive-hold";
+pub const TAPHOLD_HOLD_ON_OTHER_KEY_PRESS_FLAG: &str = "hold-on-other-key-press";
+pub const MODE_STATEMENT: &str = "mode";
+pub const MODE_END_STATEMENT: &str = "endmode";
+pub const MODE_ENTER_STATEMENT: &str = "@enter";
+pub const MODE_ESCAPE_STATEMENT: &str = "@escape";
+pub const MODE_ONEOFF_FLAG: &str = "oneoff";
+pub const TYPE_STATEMENT: &str = "@type";
+pub const KEYS_STATEMENT: &str = "@keys";
+pub const KEYCODE_PREFIX: &str = "code:";
+
+// Section headers start with their name, like `[device ...]`, so a binding of the `[` key like
+// `[` or `[ + a` is not one
+fn is_section_header(line: &str) -> bool {
+    line.strip_prefix('[').is_some_and(|rest| rest.starts_with(char::is_alphabetic))
+}
+
+// Highest keycode evdev knows about
+const KEY_MAX: u16 = 0x2ff;
+
+// Scrolling has no evdev key, so one scroll step is bound as a press of a keycode that evdev
+// leaves unused
+pub const SCROLL_UP: evdev::Key = evdev::Key::new(0x2f0);
+pub const SCROLL_DOWN: evdev::Key = evdev::Key::new(0x2f1);
+pub const SCROLL_LEFT: evdev::Key = evdev::Key::new(0x2f2);
+pub const SCROLL_RIGHT: evdev::Key = evdev::Key::new(0x2f3);
+
+// Mouse buttons, numbered like X11 and sxhkd do
+pub const POINTER_BUTTONS: [(&str, evdev::Key); 9] = [
+    ("button1", evdev::Key::BTN_LEFT),
+    ("button2", evdev::Key::BTN_MIDDLE),
+    ("button3", evdev::Key::BTN_RIGHT),
+    ("button4", SCROLL_UP),
+    ("button5", SCROLL_DOWN),
+    ("button6", SCROLL_LEFT),
+    ("button7", SCROLL_RIGHT),
+    ("button8", evdev::Key::BTN_SIDE),
+    ("button9", evdev::Key::BTN_EXTRA),
+];
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Config {
     pub path: PathBuf,
     pub contents: String,
-    pub imports: Vec<PathBuf>,
+    pub imports: Vec<Import>,
+    // `include` statements whose path could not be expanded
+    pub invalid_imports: Vec<Diagnostic>,
+    // Directories listed to find the files included by a directory or a glob
+    pub include_dirs: Vec<PathBuf>,
+    // Where the config is included: for every include statement leading to it from the root
+    // config, its line number and the index of the file among those included by its config
+    pub position: Vec<u32>,
+}
+
+// Which of the hotkeys bound to the same keys in the same mode is used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precedence {
+    // The first one, in the config and then in the files it includes, breadth first
+    First,
+    // The last one, as if every include statement was replaced by the file it includes
+    Last,
+}
+
+// An `include` statement
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+    pub path: PathBuf,
+    pub line_nr: u32,
+    // The path as written in the config, before it was expanded
+    pub written: String,
 }
 
 pub fn load_file_contents(path: &Path) -> Result<String, Error> {
@@ -76,62 +449,761 @@ pub fn load_file_contents(path: &Path) -> Result<String, Error> {
     Ok(contents)
 }
 
+// The home directory of the effective user, which is the invoking user while the config is
+// loaded. pkexec sets HOME to root's, so the password database is asked first.
+fn home_dir() -> Option<PathBuf> {
+    match User::from_uid(geteuid()) {
+        Ok(Some(user)) => Some(user.dir),
+        _ => std::env::var_os("HOME").map(PathBuf::from),
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    match name {
+        "HOME" => home_dir().map(|home| home.to_string_lossy().into_owned()),
+        "XDG_CONFIG_HOME" => std::env::var(name)
+            .ok()
+            .or_else(|| home_dir().map(|home| home.join(".config").to_string_lossy().into_owned())),
+        _ => std::env::var(name).ok(),
+    }
+}
+
+// Expands an `include` path the way a shell would: a leading `~`, and `$VAR` or `${VAR}` outside
+// of single quotes. Quotes allow spaces in the path. Errors are the error and its token.
+fn expand_path(raw: &str, path: &Path, line_nr: u32) -> Result<PathBuf, (ParseError, String)> {
+    let mut expanded = String::new();
+    let mut chars = raw.chars().peekable();
+    let mut quote: Option<char> = None;
+
+    if raw.starts_with('~') && matches!(raw[1..].chars().next(), None | Some('/')) {
+        chars.next();
+        match home_dir() {
+            Some(home) => expanded.push_str(&home.to_string_lossy()),
+            None => {
+                return Err((
+                    ParseError::UndefinedVariable(path.to_path_buf(), line_nr),
+                    "~".into(),
+                ))
+            }
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('$', None) | ('$', Some('"')) => {
+                let braced = chars.peek() == Some(&'{');
+                if braced {
+                    chars.next();
+                }
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                if braced && chars.next() != Some('}') {
+                    return Err((
+                        ParseError::UndefinedVariable(path.to_path_buf(), line_nr),
+                        format!("${{{}", name),
+                    ));
+                }
+                if name.is_empty() {
+                    expanded.push('$');
+                    continue;
+                }
+                match env_var(&name) {
+                    Some(value) => expanded.push_str(&value),
+                    None => {
+                        let token =
+                            if braced { format!("${{{}}}", name) } else { format!("${}", name) };
+                        return Err((
+                            ParseError::UndefinedVariable(path.to_path_buf(), line_nr),
+                            token,
+                        ));
+                    }
+                }
+            }
+            (c, _) => expanded.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err((ParseError::UnterminatedQuote(path.to_path_buf(), line_nr), raw.to_string()));
+    }
+
+    // Relative paths are relative to the file including them. Collecting the components drops
+    // the `.` ones, so a file is recognized however it is included.
+    let expanded = PathBuf::from(expanded);
+    let resolved = match path.parent() {
+        Some(dir) if expanded.is_relative() => dir.join(expanded),
+        _ => expanded,
+    };
+    Ok(resolved.components().collect())
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+// Shell style matching of a file name: `*` matches any characters, `?` one character, and
+// `[a-z]` or `[!a-z]` one character in or out of a set
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_match(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let end = match pattern.iter().skip(2).position(|c| *c == ']') {
+                Some(end) => end + 2,
+                // Without a closing bracket, `[` is an ordinary character
+                None => return name.first() == Some(&'[') && glob_match(&pattern[1..], &name[1..]),
+            };
+            let (negated, set) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+            let c = match name.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    in_set |= set[i] == c;
+                    i += 1;
+                }
+            }
+            in_set != negated && glob_match(&pattern[end + 1..], &name[1..])
+        }
+        Some(c) => name.first() == Some(c) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+// The entries of a directory matching a pattern, in sorted order. Hidden files only match
+// patterns starting with a `.`.
+fn list_dir(dir: &Path, pattern: &str) -> Option<Vec<PathBuf>> {
+    let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut names: Vec<String> = std::fs::read_dir(listed)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.') || pattern.first() == Some(&'.'))
+        .filter(|name| glob_match(&pattern, &name.chars().collect::<Vec<char>>()))
+        .collect();
+    names.sort();
+    Some(names.into_iter().map(|name| dir.join(name)).collect())
+}
+
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    glob_match(&pattern.chars().collect::<Vec<char>>(), &name.chars().collect::<Vec<char>>())
+}
+
+// The files an include stands for: those in a directory, those matching a glob, or else the path
+// itself. Also returns the directories listed to find them.
+fn expand_include(path: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut dirs = Vec::new();
+    if !is_glob(&path.to_string_lossy()) {
+        if !path.is_dir() {
+            return (vec![path.to_path_buf()], dirs);
+        }
+        dirs.push(path.to_path_buf());
+        let files = list_dir(path, "*").unwrap_or_default();
+        return (files.into_iter().filter(|file| file.is_file()).collect(), dirs);
+    }
+
+    let mut matches = vec![PathBuf::new()];
+    for component in path.components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !is_glob(&component) {
+            matches.iter_mut().for_each(|prefix| prefix.push(&*component));
+            continue;
+        }
+        let mut next = Vec::new();
+        for prefix in &matches {
+            if let Some(entries) = list_dir(prefix, &component) {
+                dirs.push(prefix.clone());
+                next.extend(entries);
+            }
+        }
+        matches = next;
+    }
+    (matches.into_iter().filter(|file| file.is_file()).collect(), dirs)
+}
+
 impl Config {
-    pub fn get_imports(contents: &str) -> Result<Vec<PathBuf>, Error> {
+    // The files included by the config at `path`, and the `include` statements whose path could
+    // not be expanded
+    pub fn get_imports(path: &Path, contents: &str) -> (Vec<Import>, Vec<Diagnostic>) {
         let mut imports = Vec::new();
-        for line in contents.lines() {
-            if line.split(' ').next().unwrap() == IMPORT_STATEMENT {
-                if let Some(import_path) = line.split(' ').nth(1) {
-                    imports.push(Path::new(import_path).to_path_buf());
+        let mut invalid_imports = Vec::new();
+        for (line_index, line) in contents.lines().enumerate() {
+            if line.split(' ').next().unwrap() != IMPORT_STATEMENT {
+                continue;
+            }
+            let raw = line[IMPORT_STATEMENT.len()..].trim();
+            if raw.is_empty() {
+                continue;
+            }
+            let line_nr = line_index as u32 + 1;
+            match expand_path(raw, path, line_nr) {
+                Ok(import_path) => {
+                    imports.push(Import { path: import_path, line_nr, written: raw.to_string() })
                 }
+                Err((error, token)) => invalid_imports
+                    .push(Diagnostic::new(error, &token).locate(contents, std::iter::empty())),
             }
         }
-        Ok(imports)
+        (imports, invalid_imports)
+    }
+
+    // `layout <name> [variant]`, like `layout de nodeadkeys`
+    pub fn get_layout(contents: &str) -> Option<Layout> {
+        contents.lines().find_map(|line| {
+            let mut tokens = line.split_whitespace();
+            if tokens.next() != Some(LAYOUT_STATEMENT) {
+                return None;
+            }
+            Some(Layout::new(tokens.next()?, tokens.next()))
+        })
     }
 
     pub fn new(path: &Path) -> Result<Self, Error> {
         let contents = load_file_contents(path)?;
-        let imports = Self::get_imports(&contents)?;
-        Ok(Config { path: path.to_path_buf(), contents, imports })
+        let (imports, invalid_imports) = Self::get_imports(path, &contents);
+        // A directory or glob include stands for every file it matches, in sorted order
+        let mut include_dirs = Vec::new();
+        let imports = imports
+            .into_iter()
+            .flat_map(|import| {
+                let (files, dirs) = expand_include(&import.path);
+                include_dirs.extend(dirs);
+                files.into_iter().map(move |path| Import { path, ..import.clone() })
+            })
+            .collect();
+        Ok(Config {
+            path: path.to_path_buf(),
+            contents,
+            imports,
+            invalid_imports,
+            include_dirs,
+            position: Vec::new(),
+        })
+    }
+
+    // `allow-device <matcher>` and `deny-device <matcher>`, like `deny-device id:1050:0407`
+    pub fn get_device_filter(&self) -> (DeviceFilter, Vec<Diagnostic>) {
+        let mut filter = DeviceFilter::default();
+        let mut diagnostics = Vec::new();
+        for (line_index, line) in self.contents.lines().enumerate() {
+            let (statement, matcher) = match line.split_once(char::is_whitespace) {
+                Some((statement, matcher)) => (statement, matcher.trim()),
+                None => continue,
+            };
+            let rules = match statement {
+                ALLOW_DEVICE_STATEMENT => &mut filter.allow,
+                DENY_DEVICE_STATEMENT => &mut filter.deny,
+                _ => continue,
+            };
+            match matcher.parse::<Matcher>() {
+                Ok(matcher) => rules.push(matcher),
+                Err(_) => diagnostics.push(
+                    Diagnostic::new(
+                        ParseError::UnknownSymbol(self.path.clone(), line_index as u32 + 1),
+                        matcher,
+                    )
+                    .locate(&self.contents, std::iter::empty()),
+                ),
+            }
+        }
+        (filter, diagnostics)
+    }
+
+    // `seat <name> <matcher>, <matcher>` shares the keys held on the matching devices, and
+    // `seat <name>` those held on any device
+    pub fn get_seats(&self) -> (Vec<Seat>, Vec<Diagnostic>) {
+        let mut seats = Vec::new();
+        let mut diagnostics = Vec::new();
+        for (line_index, line) in self.contents.lines().enumerate() {
+            let mut tokens = line.splitn(3, char::is_whitespace);
+            if tokens.next() != Some(SEAT_STATEMENT) {
+                continue;
+            }
+            let unknown_symbol = |token: &str| {
+                Diagnostic::new(
+                    ParseError::UnknownSymbol(self.path.clone(), line_index as u32 + 1),
+                    token,
+                )
+                .locate(&self.contents, std::iter::empty())
+            };
+            let name = match tokens.next().map(str::trim) {
+                Some(name) if !name.is_empty() => name,
+                _ => {
+                    diagnostics.push(unknown_symbol(""));
+                    continue;
+                }
+            };
+            let mut devices = Vec::new();
+            let matchers = tokens.next().unwrap_or("").split(',').map(str::trim);
+            for matcher in matchers.filter(|matcher| !matcher.is_empty()) {
+                match matcher.parse::<Matcher>() {
+                    Ok(matcher) => devices.push(matcher),
+                    Err(_) => diagnostics.push(unknown_symbol(matcher)),
+                }
+            }
+            seats.push(Seat { name: name.to_string(), devices });
+        }
+        (seats, diagnostics)
+    }
+
+    // `[device "Macro Pad", id:1050:*]` scopes the hotkeys bound below it to the devices matching
+    // one of the matchers, until the next section or the end of the file. `[device]` ends the
+    // scope. Returns the line each section starts at, and its matchers.
+    pub fn get_device_sections(&self) -> (Vec<(u32, Vec<Matcher>)>, Vec<Diagnostic>) {
+        let mut sections = Vec::new();
+        let mut diagnostics = Vec::new();
+        for (line_index, line) in self.contents.lines().enumerate() {
+            if !is_section_header(line) {
+                continue;
+            }
+            let line_nr = line_index as u32 + 1;
+            let unknown_symbol = |token: &str| {
+                Diagnostic::new(ParseError::UnknownSymbol(self.path.clone(), line_nr), token)
+                    .locate(&self.contents, [DEVICE_SECTION].into_iter())
+            };
+            let section = match line.trim_end().strip_prefix('[').and_then(|s| s.strip_suffix(']'))
+            {
+                Some(section) => section.trim(),
+                None => {
+                    diagnostics.push(unknown_symbol(line));
+                    continue;
+                }
+            };
+            let matchers = match section.split_once(char::is_whitespace) {
+                Some((DEVICE_SECTION, matchers)) => matchers,
+                None if section == DEVICE_SECTION => "",
+                _ => {
+                    let name = section.split_whitespace().next().unwrap_or("");
+                    diagnostics.push(unknown_symbol(name));
+                    continue;
+                }
+            };
+            let mut scope = Vec::new();
+            for matcher in matchers.split(',').map(str::trim).filter(|matcher| !matcher.is_empty())
+            {
+                match matcher.parse::<Matcher>() {
+                    Ok(matcher) => scope.push(matcher),
+                    Err(_) => diagnostics.push(unknown_symbol(matcher)),
+                }
+            }
+            sections.push((line_nr, scope));
+        }
+        (sections, diagnostics)
     }
 
-    pub fn load_to_configs(&self) -> Result<Vec<Self>, Error> {
-        let mut configs = Vec::new();
-        for import in &self.imports {
-            configs.push(Self::new(import)?)
+    // `precedence first` or `precedence last`
+    pub fn get_precedence(&self) -> Result<Option<Precedence>, Diagnostic> {
+        for (line_index, line) in self.contents.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            if tokens.next() != Some(PRECEDENCE_STATEMENT) {
+                continue;
+            }
+            return match tokens.next() {
+                Some("first") => Ok(Some(Precedence::First)),
+                Some("last") => Ok(Some(Precedence::Last)),
+                token => Err(Diagnostic::new(
+                    ParseError::UnknownSymbol(self.path.clone(), line_index as u32 + 1),
+                    token.unwrap_or(""),
+                )
+                .locate(&self.contents, ["first", "last"].into_iter())),
+            };
         }
-        Ok(configs)
+        Ok(None)
+    }
+
+    // A `precedence` line in an included file, which is ignored
+    fn included_precedence(&self) -> Option<Warning> {
+        let line_index = self
+            .contents
+            .lines()
+            .position(|line| line.split_whitespace().next() == Some(PRECEDENCE_STATEMENT))?;
+        Some(Warning::new(
+            self.path.clone(),
+            Some(line_index as u32 + 1),
+            String::from("`precedence` only applies in the main config, and is ignored here"),
+        ))
     }
 
-    pub fn load_and_merge(mut configs: Vec<Self>) -> Result<Vec<Self>, Error> {
-        let mut prev_count = 0;
-        let mut current_count = configs.len();
-        while prev_count != current_count {
-            prev_count = configs.len();
-            for config in configs.clone() {
-                for import in Self::load_to_configs(&config)? {
-                    if !configs.contains(&import) {
-                        configs.push(import);
+    // The config and every file it includes, each loaded once, in breadth first order. Includes
+    // that can't be read are returned as diagnostics on the `include` statement.
+    pub fn load_and_merge(root: Self) -> Result<(Vec<Self>, Vec<Diagnostic>), Error> {
+        let mut configs = vec![root];
+        let mut diagnostics = Vec::new();
+        let mut next = 0;
+        while next < configs.len() {
+            diagnostics.extend(configs[next].invalid_imports.iter().cloned());
+            let imports = configs[next].imports.clone();
+            for (index, import) in imports.into_iter().enumerate() {
+                if configs.iter().any(|config| config.path == import.path) {
+                    continue;
+                }
+                let error = match Self::new(&import.path) {
+                    Ok(mut config) => {
+                        config.position = configs[next].position.clone();
+                        config.position.extend([import.line_nr, index as u32]);
+                        configs.push(config);
+                        continue;
                     }
+                    Err(Error::ConfigNotFound) => {
+                        ParseError::MissingInclude(configs[next].path.clone(), import.line_nr)
+                    }
+                    Err(Error::Io(e)) => ParseError::UnreadableInclude(
+                        configs[next].path.clone(),
+                        import.line_nr,
+                        e.kind(),
+                    ),
+                    Err(e) => return Err(e),
+                };
+                // The expanded path is reported, under the path as it is written
+                let mut diagnostic = Diagnostic::new(error, &import.written)
+                    .locate(&configs[next].contents, std::iter::empty());
+                diagnostic.token = import.path.to_string_lossy().into_owned();
+                diagnostics.push(diagnostic);
+            }
+            next += 1;
+        }
+        Ok((configs, diagnostics))
+    }
+
+    // Includes that lead back to a file including them. They are harmless, since every file is
+    // only loaded once, but rarely intended.
+    pub fn include_cycles(configs: &[Self]) -> Vec<Warning> {
+        fn visit(
+            configs: &[Config],
+            index: usize,
+            chain: &mut Vec<usize>,
+            done: &mut Vec<bool>,
+            warnings: &mut Vec<Warning>,
+        ) {
+            chain.push(index);
+            for import in &configs[index].imports {
+                let target = match configs.iter().position(|config| config.path == import.path) {
+                    Some(target) => target,
+                    None => continue,
+                };
+                if let Some(start) = chain.iter().position(|i| *i == target) {
+                    let cycle = chain[start..]
+                        .iter()
+                        .chain([&target])
+                        .map(|i| configs[*i].path.display().to_string())
+                        .join(" -> ");
+                    warnings.push(Warning::new(
+                        configs[index].path.clone(),
+                        Some(import.line_nr),
+                        format!("include cycle {}, each file is only loaded once", cycle),
+                    ));
+                } else if !done[target] {
+                    visit(configs, target, chain, done, warnings);
                 }
             }
-            current_count = configs.len();
+            chain.pop();
+            done[index] = true;
+        }
+
+        let mut warnings = Vec::new();
+        let mut done = vec![false; configs.len()];
+        if !configs.is_empty() {
+            visit(configs, 0, &mut Vec::new(), &mut done, &mut warnings);
         }
-        Ok(configs)
+        warnings
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bindings {
+    pub hotkeys: Vec<Hotkey>,
+    pub remaps: Vec<Remap>,
+    pub tap_holds: Vec<TapHold>,
+    // The config file and every file it includes
+    pub paths: Vec<PathBuf>,
+    // Directories where new files are included as well
+    pub dirs: Vec<PathBuf>,
+    pub devices: DeviceFilter,
+    pub seats: Vec<Seat>,
+    pub warnings: Vec<Warning>,
+}
+
+#[cfg(test)]
 pub fn load(path: &Path) -> Result<Vec<Hotkey>, Error> {
-    let mut hotkeys = Vec::new();
-    let configs = vec![Config::new(path)?];
-    for config in Config::load_and_merge(configs)? {
-        for hotkey in parse_contents(path.to_path_buf(), config.contents)? {
-            if !hotkeys.contains(&hotkey) {
-                hotkeys.push(hotkey);
+    Ok(load_with_layout(path, None, None)?.hotkeys)
+}
+
+// The devices a line of a config is scoped to by its `[device]` section, any device when empty
+fn device_scope(sections: &[(u32, Vec<Matcher>)], line_nr: u32) -> Vec<Matcher> {
+    sections
+        .iter()
+        .rev()
+        .find(|(start, _)| *start <= line_nr)
+        .map(|(_, scope)| scope.clone())
+        .unwrap_or_default()
+}
+
+// Adds a hotkey to the ones bound before it, unless it is bound to the same keys in the same mode
+// and for the same devices as one of them. Then the one bound with `override` is used, or else the
+// one the precedence picks, and the other one is warned about. Returns whether the hotkey is used.
+fn merge_hotkey(
+    hotkeys: &mut Vec<Hotkey>,
+    hotkey: Hotkey,
+    precedence: Precedence,
+    path: &Path,
+    warnings: &mut Vec<Warning>,
+) -> bool {
+    let index = match hotkeys.iter().position(|i| {
+        i.keybinding == hotkey.keybinding && i.mode == hotkey.mode && i.devices == hotkey.devices
+    }) {
+        Some(index) => index,
+        None => {
+            hotkeys.push(hotkey);
+            return true;
+        }
+    };
+    let existing = &hotkeys[index];
+    // Chains sharing a prefix share the modes entered by it
+    if *existing == hotkey && hotkey.starts_chain() {
+        return true;
+    }
+
+    let replace = match (existing.overrides, hotkey.overrides) {
+        (false, true) => true,
+        (true, false) => false,
+        _ => {
+            let (used, unused) = match precedence {
+                Precedence::First => (existing, &hotkey),
+                Precedence::Last => (&hotkey, existing),
+            };
+            let binding = describe_binding(&unused.keybinding, unused.mode.as_deref());
+            let shadowing = if used.starts_chain() { "keychord chain" } else { "binding" };
+            let message = if unused.starts_chain() {
+                format!(
+                    "the keychord chain starting with {} can never be entered, it is shadowed by \
+                     the {}{}",
+                    binding,
+                    shadowing,
+                    used.describe_origin()
+                )
+            } else {
+                format!("{} is shadowed by the {}{}", binding, shadowing, used.describe_origin())
+            };
+            warnings.push(unused.warning(path, message));
+            precedence == Precedence::Last
+        }
+    };
+    if replace {
+        hotkeys[index] = hotkey;
+    }
+    replace
+}
+
+// Removes the rest of the keychord chains whose prefix is bound to something else, and the
+// prefixes of the chains left without hotkeys
+fn prune_chains(hotkeys: &mut Vec<Hotkey>) {
+    loop {
+        let entered: Vec<String> = hotkeys
+            .iter()
+            .filter_map(|hotkey| match hotkey.mode_action() {
+                Some(ModeAction::Enter { name, .. }) => Some(name),
+                _ => None,
+            })
+            .collect();
+        let used: Vec<String> = hotkeys.iter().filter_map(|hotkey| hotkey.mode.clone()).collect();
+        let count = hotkeys.len();
+        hotkeys.retain(|hotkey| {
+            let reachable = match &hotkey.mode {
+                Some(mode) if mode.contains(';') => entered.contains(mode),
+                _ => true,
+            };
+            let empty_chain = match hotkey.mode_action() {
+                Some(ModeAction::Enter { name, .. }) if hotkey.starts_chain() => {
+                    !used.contains(&name)
+                }
+                _ => false,
+            };
+            reachable && !empty_chain
+        });
+        if hotkeys.len() == count {
+            return;
+        }
+    }
+}
+
+// Keysyms are resolved through `layout` if given, else through the layout set with a `layout`
+// statement in the config, else through `default_layout`, else through the US layout.
+pub fn load_with_layout(
+    path: &Path,
+    layout: Option<&Layout>,
+    default_layout: Option<&Layout>,
+) -> Result<Bindings, Error> {
+    let mut bindings = Bindings::default();
+    let (configs, mut statement_diagnostics) = Config::load_and_merge(Config::new(path)?)?;
+    bindings.paths = configs.iter().map(|config| config.path.clone()).collect();
+    bindings.dirs =
+        configs.iter().flat_map(|config| config.include_dirs.iter().cloned()).unique().collect();
+    bindings.warnings = Config::include_cycles(&configs);
+
+    // Only the main config sets the precedence, so a shared file can't change which bindings win
+    // in the configs including it
+    let precedence = match configs[0].get_precedence() {
+        Ok(found) => found.unwrap_or(Precedence::First),
+        Err(diagnostic) => {
+            statement_diagnostics.push(diagnostic);
+            Precedence::First
+        }
+    };
+    for config in &configs[1..] {
+        bindings.warnings.extend(config.included_precedence());
+    }
+    for config in &configs {
+        let (filter, found) = config.get_device_filter();
+        bindings.devices.extend(filter);
+        statement_diagnostics.extend(found);
+        let (seats, found) = config.get_seats();
+        bindings.seats.extend(seats);
+        statement_diagnostics.extend(found);
+    }
+
+    let config_layout = configs.iter().find_map(|config| Config::get_layout(&config.contents));
+    let (key_to_evdev_key, char_to_keystroke) = match layout
+        .or(config_layout.as_ref())
+        .or(default_layout)
+    {
+        Some(layout) => {
+            let layout_keysyms = layout
+                .keysyms(&Layout::xkb_root())
+                .map_err(|e| Error::InvalidLayout(e.to_string()))?;
+            (
+                key_to_evdev_key(Some(
+                    layout_keysyms.iter().map(|(name, (key, _))| (name.clone(), *key)).collect(),
+                )),
+                char_to_keystroke(Some(&layout_keysyms)),
+            )
+        }
+        None => (key_to_evdev_key(None), char_to_keystroke(None)),
+    };
+
+    // Parse errors are collected from every file, so they can all be fixed at once
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    // The hotkeys of every file, in the order the precedence applies to
+    let mut ordered_hotkeys: Vec<(Vec<u32>, Hotkey)> = Vec::new();
+    let mut unbinds: Vec<Unbind> = Vec::new();
+    for config in configs {
+        let mut file_diagnostics: Vec<Diagnostic> = statement_diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.error.path() == config.path)
+            .cloned()
+            .collect();
+        let (sections, found) = config.get_device_sections();
+        file_diagnostics.extend(found);
+        let mut collect = |error: Error| match error {
+            Error::InvalidConfig(found) => {
+                file_diagnostics.extend(found);
+                Ok(())
+            }
+            error => Err(error),
+        };
+
+        match parse_remaps_with_keysyms(config.path.clone(), &config.contents, &key_to_evdev_key) {
+            Ok(remaps) => {
+                for remap in remaps {
+                    // The first remap of a key wins, like the first hotkey does
+                    if !bindings.remaps.iter().any(|existing| existing.from == remap.from) {
+                        bindings.remaps.push(remap);
+                    }
+                }
+            }
+            Err(e) => collect(e)?,
+        }
+        match parse_tap_holds_with_keysyms(config.path.clone(), &config.contents, &key_to_evdev_key)
+        {
+            Ok(tap_holds) => {
+                for tap_hold in tap_holds {
+                    if !bindings.tap_holds.iter().any(|existing| existing.key == tap_hold.key) {
+                        bindings.tap_holds.push(tap_hold);
+                    }
+                }
+            }
+            Err(e) => collect(e)?,
+        }
+        match parse_unbinds_with_keysyms(
+            config.path.clone(),
+            &config.contents,
+            &key_to_evdev_key,
+            &sections,
+        ) {
+            Ok(found) => unbinds.extend(found),
+            Err(e) => collect(e)?,
+        }
+        match parse_contents_with_keysyms(
+            config.path.clone(),
+            config.contents,
+            &key_to_evdev_key,
+            &char_to_keystroke,
+            &sections,
+            precedence,
+        ) {
+            Ok((hotkeys, warnings)) => {
+                bindings.warnings.extend(warnings);
+                for hotkey in hotkeys {
+                    let mut position = config.position.clone();
+                    position.extend(hotkey.origin.as_ref().map(|origin| origin.line_nr));
+                    ordered_hotkeys.push((position, hotkey));
+                }
             }
+            Err(e) => collect(e)?,
         }
+
+        file_diagnostics.sort_by_key(|diagnostic| diagnostic.error.line_nr());
+        diagnostics.extend(file_diagnostics);
     }
-    Ok(hotkeys)
+    if !diagnostics.is_empty() {
+        return Err(Error::InvalidConfig(diagnostics));
+    }
+
+    if precedence == Precedence::Last {
+        ordered_hotkeys.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    for (_, hotkey) in ordered_hotkeys {
+        merge_hotkey(&mut bindings.hotkeys, hotkey, precedence, path, &mut bindings.warnings);
+    }
+    // Unbinding comes last, so it works whichever file binds the hotkey
+    for unbind in unbinds {
+        let count = bindings.hotkeys.len();
+        bindings.hotkeys.retain(|hotkey| {
+            hotkey.keybinding != unbind.keybinding
+                || hotkey.mode != unbind.mode
+                || hotkey.devices != unbind.devices
+        });
+        if bindings.hotkeys.len() == count {
+            bindings.warnings.push(Warning::new(
+                unbind.origin.path,
+                Some(unbind.origin.line_nr),
+                format!(
+                    "{} is not bound, so there is nothing to unbind",
+                    describe_binding(&unbind.keybinding, unbind.mode.as_deref())
+                ),
+            ));
+        }
+    }
+    prune_chains(&mut bindings.hotkeys);
+
+    let warnings = lint_bindings(&bindings, path);
+    bindings.warnings.extend(warnings);
+    Ok(bindings)
 }
 
 #[derive(Debug, Clone)]
@@ -152,6 +1224,30 @@ impl PartialEq for KeyBinding {
     }
 }
 
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{} + ", modifier)?;
+        }
+        if self.on_release {
+            write!(f, "@")?;
+        }
+        if self.send {
+            write!(f, "~")?;
+        }
+        // Mouse buttons and keys without an evdev constant are shown the way they are written in
+        // the config
+        let name = format!("{:?}", self.keysym);
+        if let Some((button, _)) = POINTER_BUTTONS.iter().find(|(_, key)| *key == self.keysym) {
+            write!(f, "{}", button)
+        } else if name.parse::<evdev::Key>().ok() == Some(self.keysym) {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "{}{}", KEYCODE_PREFIX, self.keysym.code())
+        }
+    }
+}
+
 pub trait Prefix {
     fn send(self) -> Self;
     fn on_release(self) -> Self;
@@ -200,10 +1296,123 @@ impl Value for KeyBinding {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Hotkey {
     pub keybinding: KeyBinding,
     pub command: String,
+    // None means the hotkey is active outside of any mode
+    pub mode: Option<String>,
+    // Keys sent through the virtual keyboard instead of running the command, for `@type` and
+    // `@keys` commands
+    pub keystrokes: Option<Vec<KeyStroke>>,
+    // Where the hotkey is bound, for hotkeys parsed from a config
+    pub origin: Option<Origin>,
+    // Bound with `override`, so it replaces the other hotkeys bound to its keys
+    pub overrides: bool,
+    // The devices the hotkey triggers on, from its `[device]` section. Empty means any device.
+    pub devices: Vec<Matcher>,
+}
+
+// Hotkeys bound the same way in several places are the same hotkey
+impl PartialEq for Hotkey {
+    fn eq(&self, other: &Self) -> bool {
+        self.keybinding == other.keybinding
+            && self.command == other.command
+            && self.mode == other.mode
+            && self.keystrokes == other.keystrokes
+            && self.devices == other.devices
+    }
+}
+
+// The file and line of the keysym line of a hotkey
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub path: PathBuf,
+    pub line_nr: u32,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line_nr)
+    }
+}
+
+// A key pressed and released while the modifier keys are held
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyStroke {
+    pub key: evdev::Key,
+    pub modifier_keys: Vec<evdev::Key>,
+}
+
+impl KeyStroke {
+    pub fn new(key: evdev::Key, modifier_keys: Vec<evdev::Key>) -> Self {
+        KeyStroke { key, modifier_keys }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModeAction {
+    // oneoff modes are left as soon as one of their hotkeys runs a command
+    Enter { name: String, oneoff: bool },
+    Escape,
+}
+
+// `unbind super + q` removes the hotkey bound to super + q, in whichever file it is bound
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unbind {
+    pub keybinding: KeyBinding,
+    pub mode: Option<String>,
+    pub origin: Origin,
+    pub devices: Vec<Matcher>,
+}
+
+// `remap ctrl + h -> backspace` turns ctrl + h into backspace on the output device
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remap {
+    pub from: KeyBinding,
+    pub to: KeyBinding,
+}
+
+impl Remap {
+    pub fn new(from: KeyBinding, to: KeyBinding) -> Self {
+        Remap { from, to }
+    }
+
+    // A remap without modifiers on either side just renames the key
+    pub fn is_key_remap(&self) -> bool {
+        self.from.modifiers.is_empty() && self.to.modifiers.is_empty()
+    }
+}
+
+impl fmt::Display for Remap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.from, REMAP_ARROW, self.to)
+    }
+}
+
+// `taphold capslock escape ctrl` sends escape when capslock is tapped, and makes it a control
+// key while it is held. Holding can also enter a mode, like `taphold space space @enter nav`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TapHold {
+    pub key: evdev::Key,
+    pub tap: evdev::Key,
+    pub hold: HoldAction,
+    // Hold if another key is pressed and released while the key is held
+    pub permissive_hold: bool,
+    // Hold as soon as another key is pressed while the key is held
+    pub hold_on_other_key_press: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HoldAction {
+    Key(evdev::Key),
+    Mode(String),
+}
+
+impl TapHold {
+    pub fn new(key: evdev::Key, tap: evdev::Key, hold: HoldAction) -> Self {
+        TapHold { key, tap, hold, permissive_hold: false, hold_on_other_key_press: false }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
@@ -214,13 +1423,112 @@ pub enum Modifier {
     Shift,
 }
 
+impl Modifier {
+    // The left and right keys of the modifier
+    pub fn keys(self) -> [evdev::Key; 2] {
+        match self {
+            Modifier::Super => [evdev::Key::KEY_LEFTMETA, evdev::Key::KEY_RIGHTMETA],
+            Modifier::Alt => [evdev::Key::KEY_LEFTALT, evdev::Key::KEY_RIGHTALT],
+            Modifier::Control => [evdev::Key::KEY_LEFTCTRL, evdev::Key::KEY_RIGHTCTRL],
+            Modifier::Shift => [evdev::Key::KEY_LEFTSHIFT, evdev::Key::KEY_RIGHTSHIFT],
+        }
+    }
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Modifier::Super => "super".fmt(f),
+            Modifier::Alt => "alt".fmt(f),
+            Modifier::Control => "ctrl".fmt(f),
+            Modifier::Shift => "shift".fmt(f),
+        }
+    }
+}
+
 impl Hotkey {
     pub fn from_keybinding(keybinding: KeyBinding, command: String) -> Self {
-        Hotkey { keybinding, command }
+        Hotkey {
+            keybinding,
+            command,
+            mode: None,
+            keystrokes: None,
+            origin: None,
+            overrides: false,
+            devices: Vec::new(),
+        }
     }
     #[cfg(test)]
     pub fn new(keysym: evdev::Key, modifiers: Vec<Modifier>, command: String) -> Self {
-        Hotkey { keybinding: KeyBinding::new(keysym, modifiers), command }
+        Hotkey {
+            keybinding: KeyBinding::new(keysym, modifiers),
+            command,
+            mode: None,
+            keystrokes: None,
+            origin: None,
+            overrides: false,
+            devices: Vec::new(),
+        }
+    }
+    pub fn in_mode(mut self, mode: Option<String>) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn with_keystrokes(mut self, keystrokes: Option<Vec<KeyStroke>>) -> Self {
+        self.keystrokes = keystrokes;
+        self
+    }
+    pub fn overriding(mut self, overrides: bool) -> Self {
+        self.overrides = overrides;
+        self
+    }
+    pub fn on_devices(mut self, devices: Vec<Matcher>) -> Self {
+        self.devices = devices;
+        self
+    }
+    pub fn bound_at(mut self, path: &Path, line_nr: u32) -> Self {
+        self.origin = Some(Origin { path: path.to_path_buf(), line_nr });
+        self
+    }
+
+    // A warning about the hotkey, at the line binding it
+    fn warning(&self, fallback: &Path, message: String) -> Warning {
+        match &self.origin {
+            Some(origin) => Warning::new(origin.path.clone(), Some(origin.line_nr), message),
+            None => Warning::new(fallback.to_path_buf(), None, message),
+        }
+    }
+
+    // Where the hotkey is bound, for messages about it
+    fn describe_origin(&self) -> String {
+        match &self.origin {
+            Some(origin) => format!(" at {}", origin),
+            None => String::new(),
+        }
+    }
+
+    pub fn triggers_on(&self, device: &DeviceInfo) -> bool {
+        self.devices.is_empty() || self.devices.iter().any(|matcher| matcher.matches(device))
+    }
+
+    // The first chords of a keychord chain enter a mode named after the chords so far
+    fn starts_chain(&self) -> bool {
+        matches!(self.mode_action(), Some(ModeAction::Enter { name, .. }) if name.contains(';'))
+    }
+
+    // Hotkeys whose command is `@enter <mode> [oneoff]` or `@escape` change the active mode
+    // in the daemon instead of being sent to the server.
+    pub fn mode_action(&self) -> Option<ModeAction> {
+        let mut tokens = self.command.split_whitespace();
+        match tokens.next() {
+            Some(MODE_ENTER_STATEMENT) => {
+                let name = tokens.next()?.to_string();
+                let oneoff = tokens.next() == Some(MODE_ONEOFF_FLAG);
+                Some(ModeAction::Enter { name, oneoff })
+            }
+            Some(MODE_ESCAPE_STATEMENT) => Some(ModeAction::Escape),
+            _ => None,
+        }
     }
 }
 
@@ -250,119 +1558,87 @@ impl Value for &Hotkey {
     }
 }
 
-pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Error> {
-    let key_to_evdev_key: HashMap<&str, evdev::Key> = HashMap::from([
-        ("q", evdev::Key::KEY_Q),
-        ("w", evdev::Key::KEY_W),
-        ("e", evdev::Key::KEY_E),
-        ("r", evdev::Key::KEY_R),
-        ("t", evdev::Key::KEY_T),
-        ("y", evdev::Key::KEY_Y),
-        ("u", evdev::Key::KEY_U),
-        ("i", evdev::Key::KEY_I),
-        ("o", evdev::Key::KEY_O),
-        ("p", evdev::Key::KEY_P),
-        ("a", evdev::Key::KEY_A),
-        ("s", evdev::Key::KEY_S),
-        ("d", evdev::Key::KEY_D),
-        ("f", evdev::Key::KEY_F),
-        ("g", evdev::Key::KEY_G),
-        ("h", evdev::Key::KEY_H),
-        ("j", evdev::Key::KEY_J),
-        ("k", evdev::Key::KEY_K),
-        ("l", evdev::Key::KEY_L),
-        ("z", evdev::Key::KEY_Z),
-        ("x", evdev::Key::KEY_X),
-        ("c", evdev::Key::KEY_C),
-        ("v", evdev::Key::KEY_V),
-        ("b", evdev::Key::KEY_B),
-        ("n", evdev::Key::KEY_N),
-        ("m", evdev::Key::KEY_M),
-        ("1", evdev::Key::KEY_1),
-        ("2", evdev::Key::KEY_2),
-        ("3", evdev::Key::KEY_3),
-        ("4", evdev::Key::KEY_4),
-        ("5", evdev::Key::KEY_5),
-        ("6", evdev::Key::KEY_6),
-        ("7", evdev::Key::KEY_7),
-        ("8", evdev::Key::KEY_8),
-        ("9", evdev::Key::KEY_9),
-        ("0", evdev::Key::KEY_0),
-        ("escape", evdev::Key::KEY_ESC),
-        ("backspace", evdev::Key::KEY_BACKSPACE),
+pub fn keysym_from_name(name: &str) -> Option<evdev::Key> {
+    key_to_evdev_key(None).get(name.to_lowercase().as_str()).copied().or_else(|| raw_keycode(name))
+}
+
+// Mouse buttons and scroll steps are only seen on pointer devices, which swhkd only grabs when
+// they are bound
+pub fn is_pointer_key(key: evdev::Key) -> bool {
+    (evdev::Key::BTN_LEFT.code()..=evdev::Key::BTN_TASK.code()).contains(&key.code())
+        || [SCROLL_UP, SCROLL_DOWN, SCROLL_LEFT, SCROLL_RIGHT].contains(&key)
+}
+
+// Keys missing from the keysym table can be named by their evdev keycode, like `code:183`, or by
+// their evdev constant, like `KEY_PROG1` or `BTN_SIDE`.
+pub fn raw_keycode(name: &str) -> Option<evdev::Key> {
+    let name = name.trim();
+    let prefix = name.get(..KEYCODE_PREFIX.len())?;
+    if prefix.eq_ignore_ascii_case(KEYCODE_PREFIX) {
+        return match name[KEYCODE_PREFIX.len()..].parse::<u16>() {
+            Ok(code) if code <= KEY_MAX => Some(evdev::Key::new(code)),
+            _ => None,
+        };
+    }
+    let name = name.to_uppercase();
+    if name.starts_with("KEY_") || name.starts_with("BTN_") {
+        return name.parse::<evdev::Key>().ok();
+    }
+    None
+}
+
+// X11 keysym names come from the keyboard layout if one is given, and from the generated US
+// table in keysyms.rs otherwise or when the layout lacks a keysym. The aliases below are names
+// swhkd accepted before that.
+pub fn key_to_evdev_key(
+    layout_keysyms: Option<HashMap<String, evdev::Key>>,
+) -> HashMap<String, evdev::Key> {
+    let mut key_to_evdev_key = layout_keysyms.unwrap_or_default();
+
+    // Keysyms swhkd bound to another key than X11 does, kept so existing bindings don't move. A
+    // layout that has the keysym still binds it to its own key.
+    let legacy_keys = [("plus", evdev::Key::KEY_KPPLUS)];
+    for (name, key) in legacy_keys {
+        key_to_evdev_key.entry(name.to_string()).or_insert(key);
+    }
+
+    for (name, code) in KEYSYMS {
+        key_to_evdev_key.entry(name.to_string()).or_insert_with(|| evdev::Key::new(*code));
+    }
+
+    // Punctuation moves between layouts, so these follow the keysym they stand for
+    let keysym_aliases = [
+        ("-", "minus"),
+        ("=", "equal"),
+        ("`", "grave"),
+        (",", "comma"),
+        (".", "period"),
+        ("dot", "period"),
+        ("/", "slash"),
+        ("leftbrace", "bracketleft"),
+        ("[", "bracketleft"),
+        ("rightbrace", "bracketright"),
+        ("]", "bracketright"),
+        (";", "semicolon"),
+        ("'", "apostrophe"),
+    ];
+    for (name, keysym) in keysym_aliases {
+        if let Some(key) = key_to_evdev_key.get(keysym).copied() {
+            key_to_evdev_key.entry(name.to_string()).or_insert(key);
+        }
+    }
+
+    // Keys that have no X11 keysym on a US keyboard
+    let aliases = [
         ("capslock", evdev::Key::KEY_CAPSLOCK),
-        ("return", evdev::Key::KEY_ENTER),
         ("enter", evdev::Key::KEY_ENTER),
-        ("tab", evdev::Key::KEY_TAB),
-        ("space", evdev::Key::KEY_SPACE),
-        ("plus", evdev::Key::KEY_KPPLUS),
-        ("minus", evdev::Key::KEY_MINUS),
-        ("-", evdev::Key::KEY_MINUS),
-        ("equal", evdev::Key::KEY_EQUAL),
-        ("=", evdev::Key::KEY_EQUAL),
-        ("grave", evdev::Key::KEY_GRAVE),
-        ("`", evdev::Key::KEY_GRAVE),
-        ("print", evdev::Key::KEY_SYSRQ),
         ("volumeup", evdev::Key::KEY_VOLUMEUP),
-        ("xf86audioraisevolume", evdev::Key::KEY_VOLUMEUP),
         ("volumedown", evdev::Key::KEY_VOLUMEDOWN),
-        ("xf86audiolowervolume", evdev::Key::KEY_VOLUMEDOWN),
         ("mute", evdev::Key::KEY_MUTE),
-        ("xf86audiomute", evdev::Key::KEY_MUTE),
         ("brightnessup", evdev::Key::KEY_BRIGHTNESSUP),
-        ("xf86monbrightnessup", evdev::Key::KEY_BRIGHTNESSUP),
         ("brightnessdown", evdev::Key::KEY_BRIGHTNESSDOWN),
-        ("xf86monbrightnessdown", evdev::Key::KEY_BRIGHTNESSDOWN),
-        ("xf86audioplay", evdev::Key::KEY_PLAYPAUSE),
-        ("xf86audioprev", evdev::Key::KEY_PREVIOUSSONG),
-        ("xf86audionext", evdev::Key::KEY_NEXTSONG),
-        ("xf86audiostop", evdev::Key::KEY_STOP),
-        ("xf86audiomedia", evdev::Key::KEY_MEDIA),
-        (",", evdev::Key::KEY_COMMA),
-        ("comma", evdev::Key::KEY_COMMA),
-        (".", evdev::Key::KEY_DOT),
-        ("dot", evdev::Key::KEY_DOT),
-        ("period", evdev::Key::KEY_DOT),
-        ("/", evdev::Key::KEY_SLASH),
-        ("question", evdev::Key::KEY_QUESTION),
-        ("slash", evdev::Key::KEY_SLASH),
-        ("backslash", evdev::Key::KEY_BACKSLASH),
-        ("leftbrace", evdev::Key::KEY_LEFTBRACE),
-        ("[", evdev::Key::KEY_LEFTBRACE),
-        ("bracketleft", evdev::Key::KEY_LEFTBRACE),
-        ("rightbrace", evdev::Key::KEY_RIGHTBRACE),
-        ("]", evdev::Key::KEY_RIGHTBRACE),
-        ("bracketright", evdev::Key::KEY_RIGHTBRACE),
-        (";", evdev::Key::KEY_SEMICOLON),
-        ("semicolon", evdev::Key::KEY_SEMICOLON),
-        ("'", evdev::Key::KEY_APOSTROPHE),
-        ("apostrophe", evdev::Key::KEY_APOSTROPHE),
-        ("left", evdev::Key::KEY_LEFT),
-        ("right", evdev::Key::KEY_RIGHT),
-        ("up", evdev::Key::KEY_UP),
-        ("down", evdev::Key::KEY_DOWN),
-        ("pause", evdev::Key::KEY_PAUSE),
-        ("home", evdev::Key::KEY_HOME),
-        ("delete", evdev::Key::KEY_DELETE),
-        ("insert", evdev::Key::KEY_INSERT),
-        ("end", evdev::Key::KEY_END),
-        ("pause", evdev::Key::KEY_PAUSE),
-        ("prior", evdev::Key::KEY_PAGEDOWN),
-        ("next", evdev::Key::KEY_PAGEUP),
         ("pagedown", evdev::Key::KEY_PAGEDOWN),
         ("pageup", evdev::Key::KEY_PAGEUP),
-        ("f1", evdev::Key::KEY_F1),
-        ("f2", evdev::Key::KEY_F2),
-        ("f3", evdev::Key::KEY_F3),
-        ("f4", evdev::Key::KEY_F4),
-        ("f5", evdev::Key::KEY_F5),
-        ("f6", evdev::Key::KEY_F6),
-        ("f7", evdev::Key::KEY_F7),
-        ("f8", evdev::Key::KEY_F8),
-        ("f9", evdev::Key::KEY_F9),
-        ("f10", evdev::Key::KEY_F10),
-        ("f11", evdev::Key::KEY_F11),
-        ("f12", evdev::Key::KEY_F12),
         ("f13", evdev::Key::KEY_F13),
         ("f14", evdev::Key::KEY_F14),
         ("f15", evdev::Key::KEY_F15),
@@ -375,9 +1651,173 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
         ("f22", evdev::Key::KEY_F22),
         ("f23", evdev::Key::KEY_F23),
         ("f24", evdev::Key::KEY_F24),
-    ]);
+    ];
+    for (name, key) in aliases.into_iter().chain(POINTER_BUTTONS) {
+        key_to_evdev_key.entry(name.to_string()).or_insert(key);
+    }
+    key_to_evdev_key
+}
+
+#[cfg(test)]
+pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Error> {
+    Ok(parse_contents_with_keysyms(
+        path,
+        contents,
+        &key_to_evdev_key(None),
+        &char_to_keystroke(None),
+        &[],
+        Precedence::First,
+    )?
+    .0)
+}
+
+// Keysyms typed with shift on a US keyboard, since the generated table does not keep levels
+const US_SHIFTED_CHARS: &str = "~!@#$%^&*()_+{}|:\"<>?";
+
+// How to type each character, for `@type`. Without a layout, this assumes a US keyboard.
+fn char_to_keystroke(
+    layout_keysyms: Option<&HashMap<String, (evdev::Key, usize)>>,
+) -> HashMap<char, KeyStroke> {
+    let mut char_to_keystroke = HashMap::new();
+    let mut add = |keysym: &str, key: evdev::Key, level: usize| {
+        if let Some(c) = keysym_to_char(keysym) {
+            let modifier_keys = match level {
+                0 => vec![],
+                1 => vec![evdev::Key::KEY_LEFTSHIFT],
+                2 => vec![evdev::Key::KEY_RIGHTALT],
+                _ => vec![evdev::Key::KEY_RIGHTALT, evdev::Key::KEY_LEFTSHIFT],
+            };
+            char_to_keystroke.entry(c).or_insert_with(|| KeyStroke::new(key, modifier_keys));
+        }
+    };
+    match layout_keysyms {
+        Some(layout_keysyms) => {
+            for (keysym, (key, level)) in layout_keysyms {
+                add(keysym, *key, *level);
+            }
+        }
+        None => {
+            for (keysym, code) in KEYSYMS {
+                let shifted = keysym_to_char(keysym).is_some_and(|c| US_SHIFTED_CHARS.contains(c));
+                add(keysym, evdev::Key::new(*code), shifted as usize);
+            }
+        }
+    }
+    char_to_keystroke.insert('\n', KeyStroke::new(evdev::Key::KEY_ENTER, vec![]));
+    char_to_keystroke.insert('\t', KeyStroke::new(evdev::Key::KEY_TAB, vec![]));
+    char_to_keystroke
+}
+
+// Keysyms are lowercase, so uppercase letters are typed as their lowercase letter with shift
+fn type_char(c: char, char_to_keystroke: &HashMap<char, KeyStroke>) -> Option<KeyStroke> {
+    if let Some(keystroke) = char_to_keystroke.get(&c) {
+        return Some(keystroke.clone());
+    }
+    let mut lowercase = c.to_lowercase();
+    let lowercase = match (lowercase.next(), lowercase.next()) {
+        (Some(lowercase), None) if lowercase != c => lowercase,
+        _ => return None,
+    };
+    let mut keystroke = char_to_keystroke.get(&lowercase)?.clone();
+    keystroke.modifier_keys.push(evdev::Key::KEY_LEFTSHIFT);
+    Some(keystroke)
+}
+
+// `@type "text"` types the text, quoted or not. `@keys ctrl + c ctrl + v` presses the keys one
+// after the other. Returns None for other commands.
+fn parse_key_action(
+    path: PathBuf,
+    command: &str,
+    line_nr: u32,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
+    char_to_keystroke: &HashMap<char, KeyStroke>,
+) -> Result<Option<Vec<KeyStroke>>, Diagnostic> {
+    let command = command.trim();
+    let unknown_symbol =
+        |token: &str| Diagnostic::new(ParseError::UnknownSymbol(path.clone(), line_nr), token);
+
+    if let Some(text) = command.strip_prefix(TYPE_STATEMENT) {
+        if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+            return Ok(None);
+        }
+        let text = text.trim();
+        let text = match text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+            Some(quoted) => unescape(quoted),
+            None => text.to_string(),
+        };
+        return text
+            .chars()
+            .map(|c| type_char(c, char_to_keystroke).ok_or_else(|| unknown_symbol(&c.to_string())))
+            .collect::<Result<Vec<KeyStroke>, Diagnostic>>()
+            .map(Some);
+    }
 
-    let mod_to_mod_enum: HashMap<&str, Modifier> = HashMap::from([
+    if let Some(keys) = command.strip_prefix(KEYS_STATEMENT) {
+        if !keys.is_empty() && !keys.starts_with(char::is_whitespace) {
+            return Ok(None);
+        }
+        // `ctrl + c` and `ctrl+c` are both one keystroke
+        let keys = keys.split('+').map(|key| key.trim()).join("+");
+        let mod_to_mod_enum = mod_to_mod_enum();
+        let mut keystrokes = Vec::new();
+        for keys in keys.split_whitespace() {
+            let keybinding =
+                parse_keybind(path.clone(), keys, line_nr, key_to_evdev_key, &mod_to_mod_enum)?;
+            let modifier_keys =
+                keybinding.modifiers.iter().map(|modifier| modifier.keys()[0]).collect();
+            keystrokes.push(KeyStroke::new(keybinding.keysym, modifier_keys));
+        }
+        if keystrokes.is_empty() {
+            return Err(unknown_symbol(""));
+        }
+        return Ok(Some(keystrokes));
+    }
+
+    Ok(None)
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// Locates the diagnostics of a config file, with suggestions from the keysym and modifier names
+fn invalid_config(
+    diagnostics: Vec<Diagnostic>,
+    contents: &str,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
+) -> Error {
+    let mod_to_mod_enum = mod_to_mod_enum();
+    let located = diagnostics.into_iter().map(|diagnostic| {
+        let names =
+            key_to_evdev_key.keys().map(String::as_str).chain(mod_to_mod_enum.keys().copied());
+        diagnostic.locate(contents, names)
+    });
+    // Curly braces can repeat an error in every hotkey they expand to
+    let mut unique: Vec<Diagnostic> = Vec::new();
+    for diagnostic in located {
+        if !unique.contains(&diagnostic) {
+            unique.push(diagnostic);
+        }
+    }
+    Error::InvalidConfig(unique)
+}
+
+fn mod_to_mod_enum() -> HashMap<&'static str, Modifier> {
+    HashMap::from([
         ("ctrl", Modifier::Control),
         ("control", Modifier::Control),
         ("super", Modifier::Super),
@@ -385,7 +1825,191 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
         ("alt", Modifier::Alt),
         ("mod1", Modifier::Alt),
         ("shift", Modifier::Shift),
-    ]);
+    ])
+}
+
+#[cfg(test)]
+pub fn parse_remaps(path: PathBuf, contents: &str) -> Result<Vec<Remap>, Error> {
+    parse_remaps_with_keysyms(path, contents, &key_to_evdev_key(None))
+}
+
+// Remaps are single lines of `remap <keybinding> -> <keybinding>`, and expand curly braces on
+// both sides like hotkeys do, so `remap {a,b} -> {b,a}` swaps two keys.
+fn parse_remaps_with_keysyms(
+    path: PathBuf,
+    contents: &str,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
+) -> Result<Vec<Remap>, Error> {
+    let mod_to_mod_enum = mod_to_mod_enum();
+    let mut remaps = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let line_nr = line_number as u32 + 1;
+        let line = match line.strip_prefix(REMAP_STATEMENT) {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+            _ => continue,
+        };
+        let (from, to) = match line.split_once(REMAP_ARROW) {
+            Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => (from, to),
+            _ => {
+                let token = if line.contains(REMAP_ARROW) { "" } else { line };
+                diagnostics
+                    .push(Diagnostic::new(ParseError::UnknownSymbol(path.clone(), line_nr), token));
+                continue;
+            }
+        };
+        for (from, to) in extract_curly_brace(from).iter().zip(extract_curly_brace(to).iter()) {
+            let from =
+                parse_keybind(path.clone(), from, line_nr, key_to_evdev_key, &mod_to_mod_enum);
+            let to = parse_keybind(path.clone(), to, line_nr, key_to_evdev_key, &mod_to_mod_enum);
+            match (from, to) {
+                (Ok(from), Ok(to)) => remaps.push(Remap::new(from, to)),
+                (from, to) => diagnostics.extend(from.err().into_iter().chain(to.err())),
+            }
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(invalid_config(diagnostics, contents, key_to_evdev_key));
+    }
+    Ok(remaps)
+}
+
+// `unbind <keys>`, with the keys written like those of a hotkey. Inside a mode, the hotkey of the
+// mode is unbound.
+fn parse_unbinds_with_keysyms(
+    path: PathBuf,
+    contents: &str,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
+    sections: &[(u32, Vec<Matcher>)],
+) -> Result<Vec<Unbind>, Error> {
+    let mod_to_mod_enum = mod_to_mod_enum();
+    let mut unbinds = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut current_mode: Option<String> = None;
+    for (line_number, line) in contents.lines().enumerate() {
+        let line_nr = line_number as u32 + 1;
+        let mut tokens = line.split_whitespace();
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        match tokens.next() {
+            Some(MODE_STATEMENT) => current_mode = tokens.next().map(|name| name.to_string()),
+            Some(MODE_END_STATEMENT) => current_mode = None,
+            Some(UNBIND_STATEMENT) => {}
+            _ => continue,
+        }
+        let line = match line.strip_prefix(UNBIND_STATEMENT) {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+            _ => continue,
+        };
+
+        'unbind_parse: for key in extract_curly_brace(line) {
+            let (chords, _) = split_chain(&key);
+            let mut mode = current_mode.clone();
+            for (chord_index, chord) in chords.iter().enumerate() {
+                let keybinding = match parse_keybind(
+                    path.clone(),
+                    chord,
+                    line_nr,
+                    key_to_evdev_key,
+                    &mod_to_mod_enum,
+                ) {
+                    Ok(keybinding) => keybinding,
+                    Err(diagnostic) => {
+                        diagnostics.push(diagnostic);
+                        continue 'unbind_parse;
+                    }
+                };
+                if chord_index == chords.len() - 1 {
+                    let origin = Origin { path: path.clone(), line_nr };
+                    let devices = device_scope(sections, line_nr);
+                    unbinds.push(Unbind { keybinding, mode, origin, devices });
+                    continue 'unbind_parse;
+                }
+                mode = Some(chain_mode_name(mode.as_deref(), chord));
+            }
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(invalid_config(diagnostics, contents, key_to_evdev_key));
+    }
+    Ok(unbinds)
+}
+
+#[cfg(test)]
+pub fn parse_tap_holds(path: PathBuf, contents: &str) -> Result<Vec<TapHold>, Error> {
+    parse_tap_holds_with_keysyms(path, contents, &key_to_evdev_key(None))
+}
+
+// `taphold <key> <tap key> <hold key | @enter mode> [flags]`. Modifiers like `ctrl` stand for
+// their left key here.
+fn parse_tap_holds_with_keysyms(
+    path: PathBuf,
+    contents: &str,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
+) -> Result<Vec<TapHold>, Error> {
+    let mod_to_mod_enum = mod_to_mod_enum();
+    let mut tap_holds = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let line_nr = line_number as u32 + 1;
+        let line = line.split('#').next().unwrap();
+        let mut tokens = line.split_whitespace();
+        if line.starts_with(char::is_whitespace) || tokens.next() != Some(TAPHOLD_STATEMENT) {
+            continue;
+        }
+
+        let unknown_symbol =
+            |token: &str| Diagnostic::new(ParseError::UnknownSymbol(path.clone(), line_nr), token);
+        let parse_key = |token: Option<&str>| {
+            let token = token.ok_or_else(|| unknown_symbol(""))?.to_lowercase();
+            key_to_evdev_key
+                .get(&token)
+                .copied()
+                .or_else(|| raw_keycode(&token))
+                .or_else(|| Some(mod_to_mod_enum.get(token.as_str())?.keys()[0]))
+                .ok_or_else(|| unknown_symbol(&token))
+        };
+        let parse_tap_hold = |mut tokens: std::str::SplitWhitespace| {
+            let key = parse_key(tokens.next())?;
+            let tap = parse_key(tokens.next())?;
+            let hold = match tokens.next() {
+                Some(token) if token == MODE_ENTER_STATEMENT => {
+                    HoldAction::Mode(tokens.next().ok_or_else(|| unknown_symbol(""))?.to_string())
+                }
+                token => HoldAction::Key(parse_key(token)?),
+            };
+
+            let mut tap_hold = TapHold::new(key, tap, hold);
+            for flag in tokens {
+                match flag {
+                    TAPHOLD_PERMISSIVE_HOLD_FLAG => tap_hold.permissive_hold = true,
+                    TAPHOLD_HOLD_ON_OTHER_KEY_PRESS_FLAG => tap_hold.hold_on_other_key_press = true,
+                    _ => return Err(unknown_symbol(flag)),
+                }
+            }
+            Ok(tap_hold)
+        };
+        match parse_tap_hold(tokens) {
+            Ok(tap_hold) => tap_holds.push(tap_hold),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(invalid_config(diagnostics, contents, key_to_evdev_key));
+    }
+    Ok(tap_holds)
+}
+
+fn parse_contents_with_keysyms(
+    path: PathBuf,
+    contents: String,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
+    char_to_keystroke: &HashMap<char, KeyStroke>,
+    sections: &[(u32, Vec<Matcher>)],
+    precedence: Precedence,
+) -> Result<(Vec<Hotkey>, Vec<Warning>), Error> {
+    let mod_to_mod_enum = mod_to_mod_enum();
 
     let lines: Vec<&str> = contents.split('\n').collect();
 
@@ -395,7 +2019,16 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
     let mut lines_with_types: Vec<(&str, u32)> = Vec::new();
     for (line_number, line) in lines.iter().enumerate() {
         if line.trim().starts_with('#')
+            || is_section_header(line)
             || line.split(' ').next().unwrap() == IMPORT_STATEMENT
+            || line.split(' ').next().unwrap() == LAYOUT_STATEMENT
+            || line.split(' ').next().unwrap() == PRECEDENCE_STATEMENT
+            || line.split(' ').next().unwrap() == UNBIND_STATEMENT
+            || line.split(' ').next().unwrap() == ALLOW_DEVICE_STATEMENT
+            || line.split(' ').next().unwrap() == DENY_DEVICE_STATEMENT
+            || line.split(' ').next().unwrap() == SEAT_STATEMENT
+            || line.split(' ').next().unwrap() == REMAP_STATEMENT
+            || line.split(' ').next().unwrap() == TAPHOLD_STATEMENT
             || line.trim().is_empty()
         {
             continue;
@@ -409,7 +2042,7 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
 
     // Edge case: return a blank vector if no lines detected
     if lines_with_types.is_empty() {
-        return Ok(vec![]);
+        return Ok((vec![], vec![]));
     }
 
     let mut actual_lines: Vec<(&str, u32, String)> = Vec::new();
@@ -456,6 +2089,9 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
     drop(lines);
 
     let mut hotkeys: Vec<Hotkey> = Vec::new();
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    let mut current_mode: Option<String> = None;
 
     for (i, item) in actual_lines.iter().enumerate() {
         let line_type = item.0;
@@ -466,6 +2102,20 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
             continue;
         }
 
+        // Hotkeys between `mode <name>` and `endmode` only trigger while that mode is active
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some(MODE_STATEMENT) => {
+                current_mode = tokens.next().map(|name| name.to_string());
+                continue;
+            }
+            Some(MODE_END_STATEMENT) => {
+                current_mode = None;
+                continue;
+            }
+            _ => {}
+        }
+
         let next_line = actual_lines.get(i + 1);
         if next_line.is_none() {
             break;
@@ -476,31 +2126,115 @@ pub fn parse_contents(path: PathBuf, contents: String) -> Result<Vec<Hotkey>, Er
             continue; // this should ignore keysyms that are not followed by a command
         }
 
+        let (line, overrides) = match line.strip_prefix(OVERRIDE_STATEMENT) {
+            Some(rest) if rest.starts_with(char::is_whitespace) => (rest.trim_start(), true),
+            _ => (line.as_str(), false),
+        };
         let extracted_keys = extract_curly_brace(line);
         let extracted_commands = extract_curly_brace(&next_line.2);
+        let devices = device_scope(sections, line_number + 1);
 
         'hotkey_parse: for (key, command) in extracted_keys.iter().zip(extracted_commands.iter()) {
-            let keybinding = parse_keybind(
-                path.clone(),
-                key,
-                line_number + 1,
-                &key_to_evdev_key,
-                &mod_to_mod_enum,
-            )?;
-            let hotkey = Hotkey::from_keybinding(keybinding, command.to_string());
-
-            // Ignore duplicate hotkeys
-            for i in hotkeys.iter() {
-                if i.keybinding == hotkey.keybinding {
+            let (chords, locked) = split_chain(key);
+            let mut mode = current_mode.clone();
+
+            // Every chord but the last one enters a mode that holds the rest of the chain
+            for (chord_index, chord) in chords.iter().enumerate() {
+                let keybinding = match parse_keybind(
+                    path.clone(),
+                    chord,
+                    line_number + 1,
+                    key_to_evdev_key,
+                    &mod_to_mod_enum,
+                ) {
+                    Ok(keybinding) => keybinding,
+                    Err(diagnostic) => {
+                        diagnostics.push(diagnostic);
+                        continue 'hotkey_parse;
+                    }
+                };
+
+                if chord_index == chords.len() - 1 {
+                    let keystrokes = match parse_key_action(
+                        path.clone(),
+                        command,
+                        next_line.1 + 1,
+                        key_to_evdev_key,
+                        char_to_keystroke,
+                    ) {
+                        Ok(keystrokes) => keystrokes,
+                        Err(diagnostic) => {
+                            diagnostics.push(diagnostic);
+                            continue 'hotkey_parse;
+                        }
+                    };
+                    let hotkey = Hotkey::from_keybinding(keybinding, command.to_string())
+                        .in_mode(mode.clone())
+                        .with_keystrokes(keystrokes)
+                        .overriding(overrides)
+                        .on_devices(devices.clone())
+                        .bound_at(&path, line_number + 1);
+                    merge_hotkey(&mut hotkeys, hotkey, precedence, &path, &mut warnings);
+                    continue;
+                }
+
+                let chain_mode = chain_mode_name(mode.as_deref(), chord);
+                let oneoff = !(locked && chord_index == chords.len() - 2);
+                let command = if oneoff {
+                    format!("{} {} {}", MODE_ENTER_STATEMENT, chain_mode, MODE_ONEOFF_FLAG)
+                } else {
+                    format!("{} {}", MODE_ENTER_STATEMENT, chain_mode)
+                };
+                let hotkey = Hotkey::from_keybinding(keybinding, command)
+                    .in_mode(mode.clone())
+                    .overriding(overrides)
+                    .on_devices(devices.clone())
+                    .bound_at(&path, line_number + 1);
+                if !merge_hotkey(&mut hotkeys, hotkey, precedence, &path, &mut warnings) {
                     continue 'hotkey_parse;
                 }
+                mode = Some(chain_mode);
             }
+        }
+    }
 
-            hotkeys.push(hotkey);
+    if !diagnostics.is_empty() {
+        return Err(invalid_config(diagnostics, &contents, key_to_evdev_key));
+    }
+    Ok((hotkeys, warnings))
+}
+
+// Split a keychord chain like `super + a ; b` into its chords. Separating the last chord with
+// `:` instead of `;` locks the chain, so the last chord can be repeated until the chain is aborted.
+fn split_chain(line: &str) -> (Vec<String>, bool) {
+    let line = line.split('#').next().unwrap();
+
+    // The `:` of a raw keycode like `code:183` does not separate chords
+    let mut chords = vec![String::new()];
+    let mut locked = false;
+    for c in line.chars() {
+        let chord = chords.last_mut().unwrap();
+        let is_keycode =
+            c == ':' && chord.to_lowercase().ends_with(KEYCODE_PREFIX.trim_end_matches(':'));
+        if (c == ';' || c == ':') && !is_keycode {
+            locked = c == ':';
+            chords.push(String::new());
+        } else {
+            chord.push(c);
         }
     }
 
-    Ok(hotkeys)
+    // `;` on its own is a valid keysym, so only treat the line as a chain if no chord is empty
+    if chords.len() == 1 || chords.iter().any(|chord| chord.trim().is_empty()) {
+        return (vec![line.to_string()], false);
+    }
+
+    (chords, locked)
+}
+
+fn chain_mode_name(parent: Option<&str>, chord: &str) -> String {
+    let chord: String = chord.split_whitespace().collect::<String>().to_lowercase();
+    format!("{};{}", parent.unwrap_or(""), chord)
 }
 
 // We need to get the reference to key_to_evdev_key
@@ -510,22 +2244,39 @@ fn parse_keybind(
     path: PathBuf,
     line: &str,
     line_nr: u32,
-    key_to_evdev_key: &HashMap<&str, evdev::Key>,
+    key_to_evdev_key: &HashMap<String, evdev::Key>,
     mod_to_mod_enum: &HashMap<&str, Modifier>,
-) -> Result<KeyBinding, Error> {
-    let line = line.split('#').next().unwrap();
-    let tokens: Vec<String> =
-        line.split('+').map(|s| s.trim().to_lowercase()).filter(|s| s != "_").collect();
+) -> Result<KeyBinding, Diagnostic> {
+    let keybinding = line.split('#').next().unwrap().trim();
 
+    // The tokens with their character columns in the keybinding, for diagnostics
     let mut tokens_new = Vec::new();
-    for mut token in tokens {
-        while token.trim().starts_with('_') {
-            token = token.trim().strip_prefix('_').unwrap().to_string();
+    let mut spans = Vec::new();
+    let mut blank = None;
+    let mut start = 0;
+    for part in keybinding.split('+') {
+        let part_end = start + part.chars().count();
+        let rest = part.trim_start_matches(|c: char| c.is_whitespace() || c == '_');
+        let token = rest.trim_end();
+        let token_start = part_end - rest.chars().count();
+        let blank_start = part_end - part.trim_start().chars().count();
+        start = part_end + 1;
+        if part.trim() == "_" {
+            blank.get_or_insert(blank_start..blank_start + 1);
+            continue;
         }
-        tokens_new.push(token.trim().to_string());
+        tokens_new.push(token.to_lowercase());
+        spans.push(token_start..token_start + token.chars().count());
     }
 
-    let last_token = tokens_new.last().unwrap().trim();
+    // A keybinding of only `_` has no key to bind
+    let last_token = match tokens_new.last() {
+        Some(token) => token.trim(),
+        None => {
+            return Err(Diagnostic::new(ParseError::UnknownSymbol(path, line_nr), "_")
+                .in_keybinding(keybinding, blank.unwrap_or(0..1)));
+        }
+    };
 
     // Check if last_token is prefixed with @ or ~ or even both.
     // If prefixed @, on_release = true; if prefixed ~, send = true
@@ -557,33 +2308,41 @@ fn parse_keybind(
 
     let last_token = strip_at(last_token);
 
+    let resolve = |token: &str| key_to_evdev_key.get(token).copied().or_else(|| raw_keycode(token));
+
     // Check if each token is valid
-    for token in &tokens_new {
+    for (index, (token, span)) in tokens_new.iter().zip(spans).enumerate() {
+        let prefix = token.chars().count() - strip_at(token).chars().count();
         let token = strip_at(token);
-        if key_to_evdev_key.contains_key(token) {
+        let is_last = index == tokens_new.len() - 1;
+        let error = |error| {
+            Err(Diagnostic::new(error, token)
+                .in_keybinding(keybinding, span.start + prefix..span.end))
+        };
+        if resolve(token).is_some() {
             // Can't have a keysym that's like a modifier
-            if token != last_token {
-                return Err(Error::InvalidConfig(ParseError::InvalidModifier(path, line_nr)));
+            if !is_last {
+                return error(ParseError::InvalidModifier(path, line_nr));
             }
         } else if mod_to_mod_enum.contains_key(token) {
             // Can't have a modifier that's like a keysym
-            if token == last_token {
-                return Err(Error::InvalidConfig(ParseError::InvalidKeysym(path, line_nr)));
+            if is_last {
+                return error(ParseError::InvalidKeysym(path, line_nr));
             }
         } else {
-            return Err(Error::InvalidConfig(ParseError::UnknownSymbol(path, line_nr)));
+            return error(ParseError::UnknownSymbol(path, line_nr));
         }
     }
 
     // Translate keypress into evdev key
-    let keysym = key_to_evdev_key.get(last_token).unwrap();
+    let keysym = resolve(last_token).unwrap();
 
     let modifiers: Vec<Modifier> = tokens_new[0..(tokens_new.len() - 1)]
         .iter()
         .map(|token| *mod_to_mod_enum.get(token.as_str()).unwrap())
         .collect();
 
-    let mut keybinding = KeyBinding::new(*keysym, modifiers);
+    let mut keybinding = KeyBinding::new(keysym, modifiers);
     if send {
         keybinding = keybinding.send();
     }
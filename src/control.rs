@@ -0,0 +1,64 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const RUNTIME_DIR: &str = "/etc/swhkd/runtime";
+pub const REPLY_OK: &str = "ok";
+pub const REPLY_ERROR: &str = "error";
+
+// Every request is a single line. A reply is a status line (`ok` or `error`) followed by
+// free-form text.
+
+// The control socket lives next to the pidfile, in a directory only root can access.
+pub fn socket_path(uid: u32) -> PathBuf {
+    Path::new(RUNTIME_DIR).join(format!("swhkd_{}.sock", uid))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Request {
+    Pause,
+    Resume,
+    Reload,
+    Status,
+    ListBindings,
+    ListDevices,
+    Mode(String),
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace();
+        let request = match (tokens.next(), tokens.next()) {
+            (Some("pause"), None) => Request::Pause,
+            (Some("resume"), None) => Request::Resume,
+            (Some("reload"), None) => Request::Reload,
+            (Some("status"), None) => Request::Status,
+            (Some("list-bindings"), None) => Request::ListBindings,
+            (Some("list-devices"), None) => Request::ListDevices,
+            (Some("mode"), Some(name)) => Request::Mode(name.to_string()),
+            _ => return Err(format!("Invalid request: {}", line.trim())),
+        };
+        if tokens.next().is_some() {
+            return Err(format!("Invalid request: {}", line.trim()));
+        }
+        Ok(request)
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Request::Pause => "pause".fmt(f),
+            Request::Resume => "resume".fmt(f),
+            Request::Reload => "reload".fmt(f),
+            Request::Status => "status".fmt(f),
+            Request::ListBindings => "list-bindings".fmt(f),
+            Request::ListDevices => "list-devices".fmt(f),
+            Request::Mode(name) => format!("mode {}", name).fmt(f),
+        }
+    }
+}
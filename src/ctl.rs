@@ -0,0 +1,123 @@
+use clap::{arg, Command};
+use std::{env, fs, io::prelude::*, net::Shutdown, os::unix::net::UnixStream, process::exit};
+
+mod control;
+
+// Exit codes: 0 on success, 1 if swhkd refused the request, 2 if swhkd could not be reached.
+fn main() {
+    let args = set_command_line_args().get_matches();
+
+    let request = match args.subcommand() {
+        Some(("pause", _)) => control::Request::Pause,
+        Some(("resume", _)) => control::Request::Resume,
+        Some(("reload", _)) => control::Request::Reload,
+        Some(("status", _)) => control::Request::Status,
+        Some(("list-bindings", _)) => control::Request::ListBindings,
+        Some(("list-devices", _)) => control::Request::ListDevices,
+        Some(("mode", sub_args)) => {
+            control::Request::Mode(sub_args.value_of("name").unwrap().to_string())
+        }
+        _ => unreachable!(),
+    };
+
+    let uid = match args.value_of("uid") {
+        Some(uid) => uid.parse::<u32>().unwrap_or_else(|_| {
+            eprintln!("Invalid UID: {}", uid);
+            exit(2);
+        }),
+        None => find_daemon_uid().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(2);
+        }),
+    };
+
+    let socket_path = control::socket_path(uid);
+    let reply = match send_request(&socket_path, &request) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("Unable to talk to swhkd through {:?}: {}", socket_path, e);
+            eprintln!("Make sure that swhkd is running and that swhkctl is run as root.");
+            exit(2);
+        }
+    };
+
+    let (status, body) = reply.split_once('\n').unwrap_or((&reply, ""));
+    let body = body.trim_end();
+    match status {
+        control::REPLY_OK => {
+            if !body.is_empty() {
+                println!("{}", body);
+            }
+        }
+        control::REPLY_ERROR => {
+            eprintln!("{}", body);
+            exit(1);
+        }
+        _ => {
+            eprintln!("Malformed reply from swhkd: {}", reply);
+            exit(2);
+        }
+    }
+}
+
+fn send_request(
+    socket_path: &std::path::Path,
+    request: &control::Request,
+) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    writeln!(stream, "{}", request)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+// swhkd runs one instance per invoking user, so pick the user that invoked swhkctl through
+// pkexec or sudo, or the only running instance if there is just one.
+fn find_daemon_uid() -> Result<u32, String> {
+    for var in ["PKEXEC_UID", "SUDO_UID"] {
+        if let Ok(uid) = env::var(var) {
+            return uid.parse::<u32>().map_err(|_| format!("Invalid {}: {}", var, uid));
+        }
+    }
+
+    let entries = fs::read_dir(control::RUNTIME_DIR)
+        .map_err(|e| format!("Unable to read {}: {}", control::RUNTIME_DIR, e))?;
+    let uids: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix("swhkd_")?.strip_suffix(".sock")?.parse::<u32>().ok()
+        })
+        .collect();
+
+    match uids.as_slice() {
+        [uid] => Ok(*uid),
+        [] => Err(String::from("No running swhkd instance found.")),
+        _ => Err(String::from("Several swhkd instances are running, pick one with --uid.")),
+    }
+}
+
+pub fn set_command_line_args() -> Command<'static> {
+    let app = Command::new("swhkctl")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Control a running Simple Wayland HotKey Daemon")
+        .arg(
+            arg!(-u --uid <UID>)
+                .required(false)
+                .takes_value(true)
+                .help("Control the swhkd instance of this user."),
+        )
+        .subcommand_required(true)
+        .subcommand(Command::new("pause").about("Pause key checking and release the keyboards."))
+        .subcommand(Command::new("resume").about("Grab the keyboards and resume key checking."))
+        .subcommand(Command::new("reload").about("Reload the config file."))
+        .subcommand(Command::new("status").about("Show the state of the daemon."))
+        .subcommand(Command::new("list-bindings").about("List all loaded hotkeys."))
+        .subcommand(Command::new("list-devices").about("List all grabbed keyboards."))
+        .subcommand(
+            Command::new("mode").about("Enter a mode.").arg(arg!(<name> "Name of the mode.")),
+        );
+    app
+}
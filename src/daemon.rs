@@ -1,7 +1,16 @@
-use clap::{arg, Command};
-use evdev::{AttributeSet, Device, InputEventKind, Key};
+use clap::{arg, ArgMatches, Command};
+use evdev::{
+    uinput::VirtualDevice, AttributeSet, Device, EventStream, EventType, InputEvent,
+    InputEventKind, Key,
+};
+use itertools::Itertools;
 use nix::{
-    sys::stat::{umask, Mode},
+    errno::Errno,
+    sys::{
+        inotify::{AddWatchFlags, InitFlags, Inotify},
+        signal::{raise, Signal},
+        stat::{umask, Mode},
+    },
     unistd::{Group, Uid},
 };
 use signal_hook_tokio::Signals;
@@ -9,36 +18,142 @@ use std::{
     collections::{HashMap, HashSet},
     env, fs,
     io::prelude::*,
-    os::unix::net::UnixStream,
-    path::Path,
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::{Path, PathBuf},
     process::{exit, id},
+    time::SystemTime,
 };
 use sysinfo::{System, SystemExt};
+use tokio::io::{unix::AsyncFd, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::select;
 use tokio::time::Duration;
 use tokio::time::{sleep, Instant};
 use tokio_stream::{StreamExt, StreamMap};
 
 use signal_hook::consts::signal::*;
+use signal_hook::low_level::signal_name;
 
 mod config;
 use crate::config::Value;
+mod control;
+mod devices;
+mod keysyms;
+mod layout;
+mod pointer;
+mod remap;
+mod taphold;
 mod uinput;
+mod watcher;
 
 #[cfg(test)]
 mod tests;
 
+const INPUT_DIR: &str = "/dev/input";
+
+// Signals that make swhkd exit, once it cleaned up
+const TERMINATION_SIGNALS: [i32; 3] = [SIGTERM, SIGINT, SIGQUIT];
+// Job control signals that stop swhkd, like Ctrl+Z in the terminal running it
+const STOP_SIGNALS: [i32; 3] = [SIGTSTP, SIGTTIN, SIGTTOU];
+// Signals whose default action would kill swhkd for no good reason
+const IGNORED_SIGNALS: [i32; 4] = [SIGCHLD, SIGPIPE, SIGVTALRM, SIGXFSZ];
+
+// Editors can write a file several times when saving it, so reloads wait for them to settle
+const CONFIG_RELOAD_DELAY: Duration = Duration::from_millis(200);
+
 struct KeyboardState {
+    // What the device is, for hotkeys bound to some devices only
+    device: devices::DeviceInfo,
     state_modifiers: HashSet<config::Modifier>,
+    state_modifier_keys: AttributeSet<evdev::Key>,
     state_keysyms: AttributeSet<evdev::Key>,
+    active_remaps: HashMap<evdev::Key, remap::ActiveRemap>,
 }
 
 impl KeyboardState {
-    fn new() -> KeyboardState {
-        KeyboardState { state_modifiers: HashSet::new(), state_keysyms: AttributeSet::new() }
+    fn new(device: devices::DeviceInfo) -> KeyboardState {
+        KeyboardState {
+            device,
+            state_modifiers: HashSet::new(),
+            state_modifier_keys: AttributeSet::new(),
+            state_keysyms: AttributeSet::new(),
+            active_remaps: HashMap::new(),
+        }
+    }
+}
+
+// Removes the state of a keyboard that is gone, and returns the releases of the keys it still held
+// on the virtual keyboard, unless another keyboard holds them too
+fn remove_keyboard_state(
+    keyboard_states: &mut HashMap<PathBuf, KeyboardState>,
+    path: &Path,
+) -> Vec<InputEvent> {
+    let state = match keyboard_states.remove(path) {
+        Some(state) => state,
+        None => return Vec::new(),
+    };
+    let held = HeldKeys::new(keyboard_states.values());
+    let remapped = keyboard_states.values().flat_map(|state| state.active_remaps.values());
+    let still_held: HashSet<Key> = remapped
+        .flat_map(|active| active.pressed_keys())
+        .chain(held.modifier_keys.iter())
+        .chain(held.keysyms.iter())
+        .collect();
+    state
+        .state_modifier_keys
+        .iter()
+        .chain(state.state_keysyms.iter())
+        .chain(state.active_remaps.values().flat_map(|active| active.pressed_keys()))
+        .filter(|key| !still_held.contains(key))
+        .unique()
+        .map(|key| InputEvent::new(EventType::KEY, key.code(), 0))
+        .collect()
+}
+
+// The keys held on several keyboards, as if they were held on one
+struct HeldKeys {
+    modifiers: HashSet<config::Modifier>,
+    modifier_keys: AttributeSet<evdev::Key>,
+    keysyms: AttributeSet<evdev::Key>,
+}
+
+impl HeldKeys {
+    fn new<'a>(states: impl Iterator<Item = &'a KeyboardState>) -> HeldKeys {
+        let mut held = HeldKeys {
+            modifiers: HashSet::new(),
+            modifier_keys: AttributeSet::new(),
+            keysyms: AttributeSet::new(),
+        };
+        for state in states {
+            held.modifiers.extend(state.state_modifiers.iter().copied());
+            for key in state.state_modifier_keys.iter() {
+                held.modifier_keys.insert(key);
+            }
+            for key in state.state_keysyms.iter() {
+                held.keysyms.insert(key);
+            }
+        }
+        held
     }
 }
 
+// The keyboard at the path, and the other keyboards of its seat
+fn sharing_state<'a>(
+    keyboard_states: &'a HashMap<PathBuf, KeyboardState>,
+    seats: &'a [devices::Seat],
+    path: &'a Path,
+) -> impl Iterator<Item = &'a KeyboardState> {
+    let seat = keyboard_states.get(path).and_then(|state| devices::seat_of(seats, &state.device));
+    keyboard_states.values().filter(move |state| {
+        state.device.path == path
+            || (seat.is_some() && devices::seat_of(seats, &state.device) == seat)
+    })
+}
+
+struct ActiveMode {
+    name: String,
+    oneoff: bool,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = set_command_line_args().get_matches();
@@ -51,6 +166,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
     log::trace!("Logger initialized.");
 
+    // Checking a config needs none of the privileges the daemon does
+    if args.is_present("check") {
+        exit(check_config(&args));
+    }
+
     let invoking_uid = match env::var("PKEXEC_UID") {
         Ok(uid) => {
             let uid = uid.parse::<u32>().unwrap();
@@ -105,34 +225,70 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let load_config = || {
         seteuid(invoking_uid); // Dropping privileges to invoking user.
-        let config_file_path: std::path::PathBuf = if args.is_present("config") {
-            Path::new(args.value_of("config").unwrap()).to_path_buf()
-        } else {
-            fetch_xdg_config_path()
-        };
+        let bindings = load_bindings(&args);
+        seteuid(0); // Escalating back to root after reading config file.
+        let bindings = bindings?;
 
-        log::debug!("Using config file path: {:#?}", config_file_path);
+        for hotkey in &bindings.hotkeys {
+            log::debug!("hotkey: {:#?}", hotkey);
+        }
+        for remap in &bindings.remaps {
+            log::debug!("remap: {}", remap);
+        }
+        for warning in &bindings.warnings {
+            log::warn!("{}", warning);
+        }
 
-        let hotkeys = match config::load(&config_file_path) {
-            Err(e) => {
-                log::error!("Config Error: {}", e);
-                exit(1);
-            }
-            Ok(out) => out,
-        };
+        Ok::<config::Bindings, config::Error>(bindings)
+    };
 
-        for hotkey in &hotkeys {
-            log::debug!("hotkey: {:#?}", hotkey);
+    // Without a working config there are no hotkeys to fall back on, so only startup gives up
+    let config::Bindings {
+        mut hotkeys,
+        mut remaps,
+        mut tap_holds,
+        paths: mut config_paths,
+        dirs: mut config_dirs,
+        devices: mut config_devices,
+        seats: mut config_seats,
+        ..
+    } = match load_config() {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            log::error!("Config Error: {}", e);
+            exit(1);
         }
+    };
+    let config_error_command = args.value_of("config-error-command").map(str::to_string);
 
-        hotkeys
+    // Devices picked on the command line, which apply on top of the config's
+    let mut cli_devices = devices::DeviceFilter::default();
+    for (flag, rules) in
+        [("allow-device", &mut cli_devices.allow), ("deny-device", &mut cli_devices.deny)]
+    {
+        for matcher in args.values_of(flag).into_iter().flatten() {
+            match matcher.parse::<devices::Matcher>() {
+                Ok(matcher) => rules.push(matcher),
+                Err(e) => {
+                    log::error!("Invalid --{}: {}", flag, e);
+                    exit(1);
+                }
+            }
+        }
+    }
+    let mut device_filter = cli_devices.clone();
+    device_filter.extend(config_devices);
+
+    // --share-state puts the keyboards left out of the config's seats in one more seat
+    let cli_seats: Vec<devices::Seat> = if args.is_present("share-state") {
+        vec![devices::Seat { name: String::from("default"), devices: Vec::new() }]
+    } else {
+        Vec::new()
     };
+    let mut seats: Vec<devices::Seat> = config_seats.drain(..).chain(cli_seats.clone()).collect();
 
-    let mut hotkeys = load_config();
-    seteuid(0); // Escalating back to root after reading config file.
     log::trace!("Attempting to find all keyboard file descriptors.");
-    let keyboard_devices: Vec<Device> =
-        evdev::enumerate().filter(check_device_is_keyboard).collect();
+    let keyboard_devices: Vec<(PathBuf, Device)> = enumerate_keyboards(&device_filter);
 
     let mut uinput_device = match uinput::create_uinput_device() {
         Ok(dev) => dev,
@@ -167,167 +323,747 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         250
     };
 
-    let mut signals = Signals::new(&[
-        SIGUSR1, SIGUSR2, SIGHUP, SIGABRT, SIGBUS, SIGCHLD, SIGCONT, SIGINT, SIGPIPE, SIGQUIT,
-        SIGSYS, SIGTERM, SIGTRAP, SIGTSTP, SIGVTALRM, SIGXCPU, SIGXFSZ,
-    ])?;
+    let abort_key: Key = match args.value_of("abort-key") {
+        Some(name) => match config::keysym_from_name(name) {
+            Some(key) => key,
+            None => {
+                log::error!("Unknown abort keysym: {}", name);
+                exit(1);
+            }
+        },
+        None => Key::KEY_ESC,
+    };
+
+    let chain_timeout: Option<Duration> = match args.value_of("timeout") {
+        Some(timeout) => match timeout.parse::<u64>() {
+            Ok(0) => None,
+            Ok(timeout) => Some(Duration::from_millis(timeout)),
+            Err(e) => {
+                log::error!("Invalid chain timeout {}: {}", timeout, e);
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let tapping_term = match args.value_of("tapping-term") {
+        Some(tapping_term) => match tapping_term.parse::<u64>() {
+            Ok(tapping_term) => Duration::from_millis(tapping_term),
+            Err(e) => {
+                log::error!("Invalid tapping term {}: {}", tapping_term, e);
+                exit(1);
+            }
+        },
+        None => Duration::from_millis(200),
+    };
+
+    // Faults like SIGBUS or SIGABRT keep their default action, the kernel lets go of the grabbed
+    // devices when swhkd dies
+    let mut signals = Signals::new(
+        [SIGUSR1, SIGUSR2, SIGHUP, SIGCONT]
+            .iter()
+            .chain(&TERMINATION_SIGNALS)
+            .chain(&STOP_SIGNALS)
+            .chain(&IGNORED_SIGNALS),
+    )?;
 
     let mut execution_is_paused = false;
+    // Paused by a job control stop, to resume once continued
+    let mut paused_by_stop = false;
+    // Events queued on the devices while stopped are dropped once continued, per device
+    let mut stale_before: HashMap<PathBuf, SystemTime> = HashMap::new();
     let mut last_hotkey: Option<config::Hotkey> = None;
     let mut pending_release: bool = false;
-    let mut keyboard_states: Vec<KeyboardState> = Vec::new();
+    let mut mode_stack: Vec<ActiveMode> = Vec::new();
+    let mut keyboard_states: HashMap<PathBuf, KeyboardState> = HashMap::new();
     let mut keyboard_stream_map = StreamMap::new();
+    let mut tap_hold_state = taphold::TapHolds::new();
+    // Pointer devices are only grabbed while buttons or scrolling are bound
+    let mut pointers: HashMap<PathBuf, pointer::Pointer> = HashMap::new();
+    // Key events to handle after select!, after tap-hold keys are resolved
+    let mut key_events: Vec<(PathBuf, taphold::Output)> = Vec::new();
 
-    for (i, mut device) in keyboard_devices.into_iter().enumerate() {
+    for (path, mut device) in keyboard_devices {
         let _ = device.grab();
-        keyboard_stream_map.insert(i, device.into_event_stream()?);
-        keyboard_states.push(KeyboardState::new());
+        let state = KeyboardState::new(devices::DeviceInfo::new(&path, &device));
+        keyboard_stream_map.insert(path.clone(), device.into_event_stream()?);
+        keyboard_states.insert(path, state);
     }
+    sync_pointers(
+        &hotkeys,
+        &device_filter,
+        execution_is_paused,
+        &mut keyboard_stream_map,
+        &mut pointers,
+    );
+
+    // Keyboards plugged in after startup show up as new event nodes in /dev/input
+    let input_watcher = match watch_input_devices() {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log::error!("Unable to watch {} for new keyboards: {}", INPUT_DIR, e);
+            None
+        }
+    };
+
+    // Config changes are reloaded once the files stop changing for CONFIG_RELOAD_DELAY
+    let mut config_watcher = match watcher::ConfigWatcher::new() {
+        Ok(mut config_watcher) => {
+            config_watcher.watch(&config_paths, &config_dirs);
+            Some(config_watcher)
+        }
+        Err(e) => {
+            log::error!("Unable to watch the config for changes: {}", e);
+            None
+        }
+    };
+    let mut config_reload_pending = false;
+    // Reloads happen at the top of the loop, so every way of asking for one behaves the same.
+    // Control requests asking for one are answered once it is done.
+    let mut reload_requested = false;
+    let mut reload_replies: Vec<tokio::net::UnixStream> = Vec::new();
+    // Why the last reload failed, until one succeeds
+    let mut config_error: Option<String> = None;
+
+    let (control_sender, mut control_requests) = tokio::sync::mpsc::unbounded_channel();
+    let control_listener = match listen_control_socket(invoking_uid) {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            log::error!("Unable to create control socket: {}", e);
+            None
+        }
+    };
 
     // The initial sleep duration is never read because last_hotkey is initialized to None
     let hotkey_repeat_timer = sleep(Duration::from_millis(0));
     tokio::pin!(hotkey_repeat_timer);
 
+    // Only polled while a oneoff mode (an unfinished keychord chain) is active
+    let chain_timer = sleep(Duration::from_millis(0));
+    tokio::pin!(chain_timer);
+
+    // Only polled while a tap-hold key is neither tapped nor held yet
+    let tap_hold_timer = sleep(Duration::from_millis(0));
+    tokio::pin!(tap_hold_timer);
+
+    // Only polled while a config change waits to be reloaded
+    let config_reload_timer = sleep(Duration::from_millis(0));
+    tokio::pin!(config_reload_timer);
+
     loop {
+        if reload_requested {
+            reload_requested = false;
+            // The new bindings are only swapped in once all of them parsed
+            let reply = match load_config() {
+                Ok(bindings) => {
+                    config::Bindings {
+                        hotkeys,
+                        remaps,
+                        tap_holds,
+                        paths: config_paths,
+                        dirs: config_dirs,
+                        devices: config_devices,
+                        seats: config_seats,
+                        ..
+                    } = bindings;
+                    log::info!("Config reloaded, loaded {} hotkeys.", hotkeys.len());
+                    config_error = None;
+                    mode_stack.clear();
+                    last_hotkey = None;
+                    pending_release = false;
+                    device_filter = cli_devices.clone();
+                    device_filter.extend(config_devices);
+                    seats = config_seats.drain(..).chain(cli_seats.clone()).collect();
+                    key_events.extend(sync_keyboards(
+                        &device_filter,
+                        execution_is_paused,
+                        &mut keyboard_stream_map,
+                        &mut keyboard_states,
+                        &pointers,
+                        &mut uinput_device,
+                        &mut tap_hold_state,
+                    ));
+                    sync_pointers(
+                        &hotkeys,
+                        &device_filter,
+                        execution_is_paused,
+                        &mut keyboard_stream_map,
+                        &mut pointers,
+                    );
+                    if let Some(config_watcher) = &mut config_watcher {
+                        config_watcher.watch(&config_paths, &config_dirs);
+                    }
+                    Ok(format!("Loaded {} hotkeys and {} remaps.", hotkeys.len(), remaps.len()))
+                }
+                Err(e) => {
+                    log::error!("Config Error: {}", e);
+                    log::error!("Keeping the previous hotkeys.");
+                    if let Some(command) = &config_error_command {
+                        let command = format!("{} {}", command, shell_quote(&e.to_string()));
+                        if let Err(e) = sock_send(&command) {
+                            log::error!("Failed to send the config error command to swhks: {}", e);
+                        }
+                    }
+                    config_error = Some(e.to_string());
+                    Err(format!("Config Error: {}\nKeeping the previous hotkeys.", e))
+                }
+            };
+            for stream in reload_replies.drain(..) {
+                tokio::spawn(write_control_reply(stream, reply.clone()));
+            }
+        }
+
         select! {
             _ = &mut hotkey_repeat_timer, if &last_hotkey.is_some() => {
                 let hotkey = last_hotkey.clone().unwrap();
-                if hotkey.keybinding.on_release {
+                // Mode changes and typed keys happen once per press
+                if hotkey.keybinding.on_release
+                    || hotkey.mode_action().is_some()
+                    || hotkey.keystrokes.is_some()
+                {
                     continue;
                 }
                 send_command(hotkey.clone());
                 hotkey_repeat_timer.as_mut().reset(Instant::now() + Duration::from_millis(repeat_cooldown_duration));
             }
 
+            _ = &mut chain_timer, if chain_timeout.is_some() && mode_stack.last().is_some_and(|mode| mode.oneoff) => {
+                log::debug!("Keychord chain timed out.");
+                leave_oneoff_modes(&mut mode_stack);
+            }
+
+            _ = &mut tap_hold_timer, if tap_hold_state.deadline().is_some() => {
+                key_events.extend(tap_hold_state.on_timeout());
+            }
+
+            changed = async { config_watcher.as_ref().unwrap().changed().await }, if config_watcher.is_some() => {
+                match changed {
+                    Ok(changed) => {
+                        log::debug!("Config changed: {:?}", changed);
+                        config_reload_pending = true;
+                        config_reload_timer.as_mut().reset(Instant::now() + CONFIG_RELOAD_DELAY);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to wait for config changes, no longer watching the config: {}", e);
+                        config_watcher = None;
+                    }
+                }
+            }
+
+            _ = &mut config_reload_timer, if config_reload_pending => {
+                config_reload_pending = false;
+                reload_requested = true;
+            }
+
             Some(signal) = signals.next() => {
                 match signal {
                     SIGUSR1 => {
                         execution_is_paused = true;
-                        for mut device in evdev::enumerate().filter(check_device_is_keyboard) {
-                            let _ = device.ungrab();
-                        }
+                        set_keyboards_grabbed(&mut keyboard_stream_map, false);
+                        key_events.extend(tap_hold_state.release_holds(None));
                     }
 
                     SIGUSR2 => {
                         execution_is_paused = false;
-                        for mut device in evdev::enumerate().filter(check_device_is_keyboard) {
-                            let _ = device.grab();
-                        }
+                        set_keyboards_grabbed(&mut keyboard_stream_map, true);
                     }
 
                     SIGHUP => {
-                        hotkeys = load_config();
+                        reload_requested = true;
                     }
 
-                    SIGINT => {
-                        for mut device in evdev::enumerate().filter(check_device_is_keyboard) {
-                            let _ = device.ungrab();
+                    // Stopped keyboards can't be typed on, so they are let go of first
+                    signal if STOP_SIGNALS.contains(&signal) => {
+                        log::info!("Received {}, releasing the keyboards and stopping.", signal_name(signal).unwrap_or("signal"));
+                        if !execution_is_paused {
+                            execution_is_paused = true;
+                            paused_by_stop = true;
+                            set_keyboards_grabbed(&mut keyboard_stream_map, false);
+                            key_events.extend(tap_hold_state.release_holds(None));
+                        }
+                        release_held_keys(&mut uinput_device, &mut pointers);
+                        if let Err(e) = raise(Signal::SIGSTOP) {
+                            log::error!("Unable to stop: {}", e);
                         }
-                        log::warn!("Received SIGINT signal, exiting...");
-                        exit(1);
                     }
 
-                    _ => {
-                        for mut device in evdev::enumerate().filter(check_device_is_keyboard) {
-                            let _ = device.ungrab();
+                    SIGCONT => {
+                        log::info!("Continuing.");
+                        if paused_by_stop {
+                            paused_by_stop = false;
+                            execution_is_paused = false;
+                            let resumed_at = SystemTime::now();
+                            stale_before =
+                                keyboard_stream_map.keys().map(|path| (path.clone(), resumed_at)).collect();
+                            set_keyboards_grabbed(&mut keyboard_stream_map, true);
                         }
+                    }
+
+                    signal if TERMINATION_SIGNALS.contains(&signal) => {
+                        log::warn!("Received {}, exiting...", signal_name(signal).unwrap_or("signal"));
+                        shutdown(
+                            &mut uinput_device,
+                            &mut keyboard_stream_map,
+                            &mut pointers,
+                            &pidfile,
+                            invoking_uid,
+                            0,
+                        );
+                    }
 
-                        log::warn!("Received signal: {:#?}", signal);
-                        log::warn!("Exiting...");
-                        exit(1);
+                    _ => {
+                        log::debug!("Ignoring {}.", signal_name(signal).unwrap_or("signal"));
                     }
                 }
             }
 
-            Some((i, Ok(event))) = keyboard_stream_map.next() => {
-                let keyboard_state = &mut keyboard_states[i];
+            Ok((mut stream, _)) = async { control_listener.as_ref().unwrap().accept().await }, if control_listener.is_some() => {
+                // Read in its own task, so a client that is slow to send its request doesn't hold
+                // up key handling
+                let control_sender = control_sender.clone();
+                tokio::spawn(async move {
+                    match read_control_request(&mut stream).await {
+                        Ok(request) => {
+                            let _ = control_sender.send((request, stream));
+                        }
+                        Err(e) => {
+                            log::warn!("Rejected control request: {}", e);
+                            write_control_reply(stream, Err(e)).await;
+                        }
+                    }
+                });
+            }
 
-                let key = match event.kind() {
-                    InputEventKind::Key(keycode) => keycode,
-                    _ => continue
-                };
+            Some((request, stream)) = control_requests.recv() => {
+                log::debug!("Control request: {}", request);
+                if request == control::Request::Reload {
+                    reload_requested = true;
+                    reload_replies.push(stream);
+                    continue;
+                }
 
-                match event.value() {
-                    // Key press
-                    1 => {
-                        if let Some(modifier) = modifiers_map.get(&key) {
-                            keyboard_state.state_modifiers.insert(*modifier);
+                let reply = match request {
+                    control::Request::Pause => {
+                        execution_is_paused = true;
+                        set_keyboards_grabbed(&mut keyboard_stream_map, false);
+                        key_events.extend(tap_hold_state.release_holds(None));
+                        Ok(String::from("Paused."))
+                    }
+                    control::Request::Resume => {
+                        execution_is_paused = false;
+                        set_keyboards_grabbed(&mut keyboard_stream_map, true);
+                        Ok(String::from("Resumed."))
+                    }
+                    // Answered at the top of the loop, once the config is reloaded
+                    control::Request::Reload => unreachable!(),
+                    control::Request::Status => {
+                        let modes = mode_stack.iter().map(|mode| mode.name.as_str()).join(" > ");
+                        Ok(format!(
+                            "state: {}\nmode: {}\nhotkeys: {}\nremaps: {}\ntap-hold keys: {}\nkeyboards: {}\npointers: {}\nconfig: {}",
+                            if execution_is_paused { "paused" } else { "running" },
+                            if modes.is_empty() { "none" } else { &modes },
+                            hotkeys.len(),
+                            remaps.len(),
+                            tap_holds.len(),
+                            keyboard_stream_map.len() - pointers.len(),
+                            pointers.len(),
+                            match &config_error {
+                                Some(e) => format!("error, keeping the previous hotkeys\n{}", e),
+                                None => String::from("ok"),
+                            },
+                        ))
+                    }
+                    control::Request::ListBindings => Ok(remaps
+                        .iter()
+                        .map(|remap| format!("{} {}", config::REMAP_STATEMENT, remap))
+                        .chain(hotkeys.iter().map(|hotkey| {
+                            let binding = match &hotkey.mode {
+                                Some(mode) => format!("[{}] {}\t{}", mode, hotkey.keybinding, hotkey.command),
+                                None => format!("{}\t{}", hotkey.keybinding, hotkey.command),
+                            };
+                            match &hotkey.origin {
+                                Some(origin) => format!("{}\t# {}", binding, origin),
+                                None => binding,
+                            }
+                        }))
+                        .join("\n")),
+                    control::Request::ListDevices => Ok(keyboard_stream_map
+                        .iter()
+                        .map(|(path, stream)| {
+                            format!("{}\t{}", path.display(), stream.device().name().unwrap_or("unknown"))
+                        })
+                        .join("\n")),
+                    control::Request::Mode(name) => {
+                        if hotkeys.iter().any(|hotkey| hotkey.mode.as_ref() == Some(&name)) {
+                            log::debug!("Entering mode: {}", name);
+                            mode_stack.push(ActiveMode { name, oneoff: false });
+                            Ok(String::new())
                         } else {
-                            keyboard_state.state_keysyms.insert(key);
+                            Err(format!("Unknown mode: {}", name))
                         }
                     }
+                };
+                // Written in its own task too, so a client that never reads can't stall the loop
+                tokio::spawn(write_control_reply(stream, reply));
+            }
+
+            Ok(mut guard) = async { input_watcher.as_ref().unwrap().readable().await }, if input_watcher.is_some() => {
+                let events = match guard.get_inner().read_events() {
+                    Ok(events) => events,
+                    Err(Errno::EAGAIN) => {
+                        guard.clear_ready();
+                        continue;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to read {} events: {}", INPUT_DIR, e);
+                        guard.clear_ready();
+                        continue;
+                    }
+                };
+
+                for event in events {
+                    let path = match event.name {
+                        Some(name) if name.to_string_lossy().starts_with("event") => Path::new(INPUT_DIR).join(name),
+                        _ => continue,
+                    };
 
-                    // Key release
-                    0 => {
-                        if last_hotkey.is_some() && pending_release {
-                            pending_release = false;
-                            send_command(last_hotkey.clone().unwrap());
-                            last_hotkey = None;
+                    if event.mask.contains(AddWatchFlags::IN_CREATE) {
+                        if keyboard_stream_map.contains_key(&path) {
+                            continue;
                         }
-                        if let Some(modifier) = modifiers_map.get(&key) {
-                            if let Some(hotkey) = &last_hotkey {
-                                if hotkey.modifiers().contains(modifier) {
-                                    last_hotkey = None;
-                                }
+                        let mut device = match Device::open(&path) {
+                            Ok(device) => device,
+                            Err(e) => {
+                                log::warn!("Unable to open new device {:?}: {}", path, e);
+                                continue;
                             }
-                            keyboard_state.state_modifiers.remove(modifier);
-                        } else if keyboard_state.state_keysyms.contains(key) {
-                            if let Some(hotkey) = &last_hotkey {
-                                if key == hotkey.keysym() {
-                                    last_hotkey = None;
-                                }
+                        };
+                        if !check_device_is_keyboard(&device) {
+                            if pointers_bound(&hotkeys)
+                                && check_device_is_pointer(&device)
+                                && check_device_is_allowed(&device_filter, &path, &device)
+                            {
+                                add_pointer(path, device, execution_is_paused, &mut keyboard_stream_map, &mut pointers);
                             }
-                            keyboard_state.state_keysyms.remove(key);
+                            continue;
+                        }
+                        if !check_device_is_allowed(&device_filter, &path, &device) {
+                            continue;
                         }
+                        if !execution_is_paused {
+                            let _ = device.grab();
+                        }
+                        let state = KeyboardState::new(devices::DeviceInfo::new(&path, &device));
+                        match device.into_event_stream() {
+                            Ok(stream) => {
+                                log::info!("Keyboard connected: {:?}", path);
+                                keyboard_stream_map.insert(path.clone(), stream);
+                                keyboard_states.insert(path, state);
+                            }
+                            Err(e) => log::error!("Unable to read events from {:?}: {}", path, e),
+                        }
+                    } else if event.mask.contains(AddWatchFlags::IN_DELETE)
+                        && keyboard_stream_map.remove(&path).is_some()
+                    {
+                        if pointers.remove(&path).is_some() {
+                            log::info!("Pointer disconnected: {:?}", path);
+                        } else {
+                            log::info!("Keyboard disconnected: {:?}", path);
+                        }
+                        key_events.extend(remove_keyboard(
+                            &mut uinput_device,
+                            &mut keyboard_states,
+                            &mut tap_hold_state,
+                            &path,
+                        ));
                     }
-
-                    _ => {}
                 }
+            }
 
-                let possible_hotkeys: Vec<&config::Hotkey> = hotkeys.iter()
-                    .filter(|hotkey| hotkey.modifiers().len() == keyboard_state.state_modifiers.len())
-                    .collect();
-
-                let event_in_hotkeys = hotkeys.iter().any(|hotkey| {
-                    hotkey.keysym().code() == event.code() &&
-                    keyboard_state.state_modifiers
+            Some((path, event)) = keyboard_stream_map.next() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        // The device is gone or unusable, stop polling it instead of erroring forever
+                        log::warn!("Removing device {:?} after read error: {}", path, e);
+                        keyboard_stream_map.remove(&path);
+                        key_events.extend(remove_keyboard(
+                            &mut uinput_device,
+                            &mut keyboard_states,
+                            &mut tap_hold_state,
+                            &path,
+                        ));
+                        pointers.remove(&path);
+                        continue;
+                    }
+                };
+                // Typed while swhkd was stopped, not meant as hotkeys now
+                if is_stale(&mut stale_before, &path, &event) {
+                    continue;
+                }
+                if let Some(pointer) = pointers.get_mut(&path) {
+                    // An ungrabbed pointer already reaches applications
+                    if execution_is_paused {
+                        continue;
+                    }
+                    // Modifiers are held on the keyboards of the pointer's seat, or on any keyboard
+                    // if it has none
+                    let seat = devices::seat_of(&seats, &pointer.device);
+                    let held = HeldKeys::new(keyboard_states.values().filter(|state| {
+                        seat.is_none() || devices::seat_of(&seats, &state.device) == seat
+                    }));
+                    let (modifiers, held_modifier_keys) = (held.modifiers, held.modifier_keys);
+                    let current_mode = mode_stack.last().map(|mode| mode.name.clone());
+                    let pointer_hotkeys: Vec<&config::Hotkey> = hotkeys
                         .iter()
-                        .all(|x| hotkey.modifiers().contains(x)) &&
-                    keyboard_state.state_modifiers.len() == hotkey.modifiers().len()
-                    && !hotkey.is_send()
-                        });
+                        .filter(|hotkey| hotkey.mode == current_mode)
+                        .filter(|hotkey| hotkey.triggers_on(&pointer.device))
+                        .sorted_by_key(|hotkey| hotkey.devices.is_empty())
+                        .filter(|hotkey| hotkey.modifiers().len() == modifiers.len())
+                        .filter(|hotkey| modifiers.iter().all(|x| hotkey.modifiers().contains(x)))
+                        .collect();
+
+                    let triggered = pointer.on_event(event, |key| {
+                        let mut bound = pointer_hotkeys.iter().filter(|hotkey| hotkey.keysym() == key);
+                        match bound.next() {
+                            None => pointer::Binding::Unbound,
+                            Some(hotkey) if hotkey.is_send() => pointer::Binding::Send,
+                            Some(_) => pointer::Binding::Swallow,
+                        }
+                    });
+                    let triggered = match triggered {
+                        Ok(triggered) => triggered,
+                        Err(e) => {
+                            log::error!("Failed to replay pointer events of {:?}: {}", path, e);
+                            continue;
+                        }
+                    };
+                    for (key, value) in triggered {
+                        let hotkey = pointer_hotkeys
+                            .iter()
+                            .find(|hotkey| hotkey.keysym() == key && hotkey.is_on_release() == (value == 0));
+                        if let Some(hotkey) = hotkey {
+                            run_hotkey((*hotkey).clone(), &mut mode_stack, &mut uinput_device, &held_modifier_keys);
+                            if let Some(deadline) = chain_deadline(chain_timeout, &mode_stack) {
+                                chain_timer.as_mut().reset(deadline);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                // Only key events are handled
+                if !matches!(event.kind(), InputEventKind::Key(_)) {
+                    continue;
+                }
+                if execution_is_paused {
+                    key_events.push((path, taphold::Output::Key(event)));
+                } else {
+                    key_events.extend(tap_hold_state.on_event(&tap_holds, tapping_term, &path, event));
+                }
+                if let Some(deadline) = tap_hold_state.deadline() {
+                    tap_hold_timer.as_mut().reset(deadline);
+                }
+            }
+        }
 
-                // Don't emit event to virtual device if it's from a valid hotkey
-                if !event_in_hotkeys {
-                    uinput_device.emit(&[event]).unwrap();
+        for (path, output) in key_events.drain(..) {
+            let event = match output {
+                taphold::Output::Key(event) => event,
+                taphold::Output::EnterMode(name) => {
+                    log::debug!("Entering mode: {}", name);
+                    mode_stack.push(ActiveMode { name, oneoff: false });
+                    continue;
+                }
+                taphold::Output::LeaveMode(name) => {
+                    if let Some(index) = mode_stack.iter().rposition(|mode| mode.name == name) {
+                        log::debug!("Leaving mode: {}", name);
+                        mode_stack.remove(index);
+                    }
+                    continue;
                 }
+            };
+            // Events of a device unplugged in the meantime can still come out of the tap-hold keys,
+            // and go with the device
+            if !keyboard_states.contains_key(&path) {
+                continue;
+            }
+            let held = HeldKeys::new(sharing_state(&keyboard_states, &seats, &path));
+            let keyboard_state = keyboard_states.get_mut(&path).unwrap();
+
+            let key = match event.kind() {
+                InputEventKind::Key(keycode) => keycode,
+                _ => continue,
+            };
 
-                if execution_is_paused || possible_hotkeys.is_empty() || last_hotkey.is_some() {
+            // Remaps apply before anything else, so hotkeys see the remapped keys
+            let remapped = if execution_is_paused {
+                None
+            } else {
+                remap::remap_event(
+                    &remaps,
+                    &mut keyboard_state.active_remaps,
+                    &held.modifiers,
+                    &held.modifier_keys,
+                    key,
+                    event.value(),
+                )
+            };
+            let (key, event) = match remapped {
+                Some(remap::Remapped::Key(key)) => {
+                    (key, InputEvent::new(EventType::KEY, key.code(), event.value()))
+                }
+                Some(remap::Remapped::Events(events)) => {
+                    for event in events {
+                        uinput_device.emit(&[event]).unwrap();
+                    }
                     continue;
                 }
+                None => (key, event),
+            };
 
-                log::debug!("state_modifiers: {:#?}", keyboard_state.state_modifiers);
-                log::debug!("state_keysyms: {:#?}", keyboard_state.state_keysyms);
-                log::debug!("hotkey: {:#?}", possible_hotkeys);
+            match event.value() {
+                // Key press
+                1 => {
+                    if let Some(modifier) = modifiers_map.get(&key) {
+                        keyboard_state.state_modifiers.insert(*modifier);
+                        keyboard_state.state_modifier_keys.insert(key);
+                    } else {
+                        keyboard_state.state_keysyms.insert(key);
+                    }
+                }
 
-                for hotkey in possible_hotkeys {
-                    // this should check if state_modifiers and hotkey.modifiers have the same elements
-                    if keyboard_state.state_modifiers.iter().all(|x| hotkey.modifiers().contains(x))
-                        && keyboard_state.state_modifiers.len() == hotkey.modifiers().len()
-                        && keyboard_state.state_keysyms.contains(hotkey.keysym())
-                    {
-                        last_hotkey = Some(hotkey.clone());
-                        if pending_release { break; }
-                        if hotkey.is_on_release() {
-                            pending_release = true;
-                            break;
+                // Key release
+                0 => {
+                    if last_hotkey.is_some() && pending_release {
+                        pending_release = false;
+                        run_hotkey(
+                            last_hotkey.clone().unwrap(),
+                            &mut mode_stack,
+                            &mut uinput_device,
+                            &held.modifier_keys,
+                        );
+                        if let Some(deadline) = chain_deadline(chain_timeout, &mode_stack) {
+                            chain_timer.as_mut().reset(deadline);
                         }
-                        send_command(hotkey.clone());
-                        hotkey_repeat_timer.as_mut().reset(Instant::now() + Duration::from_millis(repeat_cooldown_duration));
+                        last_hotkey = None;
+                    }
+                    if let Some(modifier) = modifiers_map.get(&key) {
+                        if let Some(hotkey) = &last_hotkey {
+                            if hotkey.modifiers().contains(modifier) {
+                                last_hotkey = None;
+                            }
+                        }
+                        keyboard_state.state_modifiers.remove(modifier);
+                        keyboard_state.state_modifier_keys.remove(key);
+                    } else if keyboard_state.state_keysyms.contains(key) {
+                        if let Some(hotkey) = &last_hotkey {
+                            if key == hotkey.keysym() {
+                                last_hotkey = None;
+                            }
+                        }
+                        keyboard_state.state_keysyms.remove(key);
+                    }
+                }
+
+                _ => {}
+            }
+
+            // Matched against the keys held on every keyboard sharing the state of this one
+            let held = HeldKeys::new(sharing_state(&keyboard_states, &seats, &path));
+            let device = &keyboard_states[&path].device;
+
+            if event.value() == 1 && key == abort_key && !mode_stack.is_empty() {
+                log::debug!("Abort key pressed, leaving all modes.");
+                mode_stack.clear();
+                last_hotkey = None;
+                pending_release = false;
+                continue;
+            }
+
+            let current_mode = mode_stack.last().map(|mode| mode.name.clone());
+            // Hotkeys bound to the device come before those bound to any device
+            let mode_hotkeys: Vec<&config::Hotkey> = hotkeys
+                .iter()
+                .filter(|hotkey| hotkey.mode == current_mode)
+                .filter(|hotkey| hotkey.triggers_on(device))
+                .sorted_by_key(|hotkey| hotkey.devices.is_empty())
+                .collect();
+
+            let possible_hotkeys: Vec<&config::Hotkey> = mode_hotkeys
+                .iter()
+                .copied()
+                .filter(|hotkey| hotkey.modifiers().len() == held.modifiers.len())
+                .collect();
+
+            let event_in_hotkeys = mode_hotkeys.iter().any(|hotkey| {
+                hotkey.keysym().code() == event.code()
+                    && held.modifiers.iter().all(|x| hotkey.modifiers().contains(x))
+                    && held.modifiers.len() == hotkey.modifiers().len()
+                    && !hotkey.is_send()
+            });
+
+            // Don't emit event to virtual device if it's from a valid hotkey
+            if !event_in_hotkeys {
+                uinput_device.emit(&[event]).unwrap();
+            }
+
+            let in_chain = mode_stack.last().is_some_and(|mode| mode.oneoff);
+            if in_chain && event.value() == 1 {
+                if let Some(deadline) = chain_deadline(chain_timeout, &mode_stack) {
+                    chain_timer.as_mut().reset(deadline);
+                }
+            }
+
+            if execution_is_paused || last_hotkey.is_some() {
+                continue;
+            }
+
+            let mut hotkey_matched = false;
+
+            log::debug!("state_modifiers: {:#?}", held.modifiers);
+            log::debug!("state_keysyms: {:#?}", held.keysyms);
+            log::debug!("hotkey: {:#?}", possible_hotkeys);
+
+            for hotkey in possible_hotkeys {
+                // this should check if state_modifiers and hotkey.modifiers have the same elements
+                if held.modifiers.iter().all(|x| hotkey.modifiers().contains(x))
+                    && held.modifiers.len() == hotkey.modifiers().len()
+                    && held.keysyms.contains(hotkey.keysym())
+                {
+                    hotkey_matched = true;
+                    last_hotkey = Some(hotkey.clone());
+                    if pending_release {
+                        break;
+                    }
+                    if hotkey.is_on_release() {
+                        pending_release = true;
                         break;
                     }
+                    run_hotkey(
+                        hotkey.clone(),
+                        &mut mode_stack,
+                        &mut uinput_device,
+                        &held.modifier_keys,
+                    );
+                    if let Some(deadline) = chain_deadline(chain_timeout, &mode_stack) {
+                        chain_timer.as_mut().reset(deadline);
+                    }
+                    hotkey_repeat_timer
+                        .as_mut()
+                        .reset(Instant::now() + Duration::from_millis(repeat_cooldown_duration));
+                    break;
                 }
             }
+
+            // Any other key press aborts an unfinished keychord chain
+            if in_chain
+                && !hotkey_matched
+                && event.value() == 1
+                && !modifiers_map.contains_key(&key)
+            {
+                log::debug!("Key is not part of the keychord chain, aborting it.");
+                leave_oneoff_modes(&mut mode_stack);
+            }
         }
     }
 }
@@ -339,6 +1075,64 @@ fn sock_send(command: &str) -> std::io::Result<()> {
     Ok(())
 }
 
+// Quotes text as a single shell word
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn run_hotkey(
+    hotkey: config::Hotkey,
+    mode_stack: &mut Vec<ActiveMode>,
+    uinput_device: &mut VirtualDevice,
+    held_modifier_keys: &AttributeSet<Key>,
+) {
+    match hotkey.mode_action() {
+        Some(action) => apply_mode_action(action, mode_stack),
+        None => {
+            match &hotkey.keystrokes {
+                Some(keystrokes) => {
+                    log::info!("Hotkey pressed: {:#?}", hotkey);
+                    if let Err(e) =
+                        uinput::emit_keystrokes(uinput_device, keystrokes, held_modifier_keys)
+                    {
+                        log::error!("Failed to send keys through the virtual keyboard: {}", e);
+                    }
+                }
+                None => send_command(hotkey),
+            }
+            leave_oneoff_modes(mode_stack);
+        }
+    }
+}
+
+fn apply_mode_action(action: config::ModeAction, mode_stack: &mut Vec<ActiveMode>) {
+    match action {
+        config::ModeAction::Enter { name, oneoff } => {
+            log::debug!("Entering mode: {}", name);
+            mode_stack.push(ActiveMode { name, oneoff });
+        }
+        config::ModeAction::Escape => {
+            if let Some(mode) = mode_stack.pop() {
+                log::debug!("Leaving mode: {}", mode.name);
+            }
+        }
+    }
+}
+
+// When the next key of a keychord chain is due, counted from now, while one is entered
+fn chain_deadline(chain_timeout: Option<Duration>, mode_stack: &[ActiveMode]) -> Option<Instant> {
+    match (chain_timeout, mode_stack.last()) {
+        (Some(timeout), Some(mode)) if mode.oneoff => Some(Instant::now() + timeout),
+        _ => None,
+    }
+}
+
+fn leave_oneoff_modes(mode_stack: &mut Vec<ActiveMode>) {
+    while mode_stack.last().is_some_and(|mode| mode.oneoff) {
+        mode_stack.pop();
+    }
+}
+
 fn send_command(hotkey: config::Hotkey) {
     log::info!("Hotkey pressed: {:#?}", hotkey);
     if let Err(e) = sock_send(&hotkey.command) {
@@ -368,9 +1162,292 @@ pub fn check_user_permissions() -> Result<(), ()> {
     }
 }
 
+pub fn enumerate_keyboards(filter: &devices::DeviceFilter) -> Vec<(PathBuf, Device)> {
+    enumerate_devices(|path, device| {
+        check_device_is_keyboard(device) && check_device_is_allowed(filter, path, device)
+    })
+}
+
+fn enumerate_devices(check_device: impl Fn(&Path, &Device) -> bool) -> Vec<(PathBuf, Device)> {
+    let entries = match fs::read_dir(INPUT_DIR) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Unable to read {}: {}", INPUT_DIR, e);
+            return Vec::new();
+        }
+    };
+
+    let mut devices = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !entry.file_name().to_string_lossy().starts_with("event") {
+            continue;
+        }
+        match Device::open(&path) {
+            Ok(device) if check_device(&path, &device) => devices.push((path, device)),
+            Ok(_) => {}
+            Err(e) => log::trace!("Unable to open {:?}: {}", path, e),
+        }
+    }
+    devices
+}
+
+fn pointers_bound(hotkeys: &[config::Hotkey]) -> bool {
+    hotkeys.iter().any(|hotkey| config::is_pointer_key(hotkey.keysym()))
+}
+
+// Lets go of the keyboards the device filter no longer allows, and grabs the ones it newly does
+fn sync_keyboards(
+    filter: &devices::DeviceFilter,
+    paused: bool,
+    stream_map: &mut StreamMap<PathBuf, EventStream>,
+    keyboard_states: &mut HashMap<PathBuf, KeyboardState>,
+    pointers: &HashMap<PathBuf, pointer::Pointer>,
+    uinput_device: &mut VirtualDevice,
+    tap_hold_state: &mut taphold::TapHolds,
+) -> Vec<(PathBuf, taphold::Output)> {
+    let mut outputs = Vec::new();
+    let denied: Vec<PathBuf> = stream_map
+        .iter()
+        .filter(|(path, stream)| {
+            !pointers.contains_key(path.as_path())
+                && !check_device_is_allowed(filter, path, stream.device())
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in denied {
+        log::info!("Releasing keyboard: {:?}", path);
+        stream_map.remove(&path);
+        outputs.extend(remove_keyboard(uinput_device, keyboard_states, tap_hold_state, &path));
+    }
+
+    let added = enumerate_devices(|path, device| {
+        !stream_map.contains_key(path)
+            && check_device_is_keyboard(device)
+            && check_device_is_allowed(filter, path, device)
+    });
+    for (path, mut device) in added {
+        if !paused {
+            let _ = device.grab();
+        }
+        let state = KeyboardState::new(devices::DeviceInfo::new(&path, &device));
+        match device.into_event_stream() {
+            Ok(stream) => {
+                log::info!("Keyboard connected: {:?}", path);
+                stream_map.insert(path.clone(), stream);
+                keyboard_states.insert(path, state);
+            }
+            Err(e) => log::error!("Unable to read events from {:?}: {}", path, e),
+        }
+    }
+    outputs
+}
+
+// Grabs the pointer devices when the hotkeys bind buttons or scrolling, and lets go of them when
+// they no longer do or the device filter no longer allows them
+pub fn sync_pointers(
+    hotkeys: &[config::Hotkey],
+    filter: &devices::DeviceFilter,
+    paused: bool,
+    stream_map: &mut StreamMap<PathBuf, EventStream>,
+    pointers: &mut HashMap<PathBuf, pointer::Pointer>,
+) {
+    if pointers_bound(hotkeys) {
+        let denied: Vec<PathBuf> = pointers
+            .keys()
+            .filter(|path| {
+                stream_map.iter().find(|(stream_path, _)| stream_path == *path).is_some_and(
+                    |(_, stream)| !check_device_is_allowed(filter, path, stream.device()),
+                )
+            })
+            .cloned()
+            .collect();
+        for path in denied {
+            log::info!("Releasing pointer: {:?}", path);
+            stream_map.remove(&path);
+            pointers.remove(&path);
+        }
+        let added = enumerate_devices(|path, device| {
+            !stream_map.contains_key(path)
+                && !check_device_is_keyboard(device)
+                && check_device_is_pointer(device)
+                && check_device_is_allowed(filter, path, device)
+        });
+        for (path, device) in added {
+            add_pointer(path, device, paused, stream_map, pointers);
+        }
+    } else {
+        for (path, _) in pointers.drain() {
+            log::info!("Releasing pointer: {:?}", path);
+            stream_map.remove(&path);
+        }
+    }
+}
+
+fn add_pointer(
+    path: PathBuf,
+    mut device: Device,
+    paused: bool,
+    stream_map: &mut StreamMap<PathBuf, EventStream>,
+    pointers: &mut HashMap<PathBuf, pointer::Pointer>,
+) {
+    let pointer = match pointer::Pointer::new(&path, &device) {
+        Ok(pointer) => pointer,
+        Err(e) => {
+            log::error!("Unable to create a virtual pointer for {:?}: {}", path, e);
+            return;
+        }
+    };
+    if !paused {
+        let _ = device.grab();
+    }
+    match device.into_event_stream() {
+        Ok(stream) => {
+            log::info!("Pointer connected: {:?}", path);
+            stream_map.insert(path.clone(), stream);
+            pointers.insert(path, pointer);
+        }
+        Err(e) => log::error!("Unable to read events from {:?}: {}", path, e),
+    }
+}
+
+pub fn watch_input_devices() -> Result<AsyncFd<Inotify>, Box<dyn std::error::Error>> {
+    let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+    inotify.add_watch(INPUT_DIR, AddWatchFlags::IN_CREATE | AddWatchFlags::IN_DELETE)?;
+    Ok(AsyncFd::new(inotify)?)
+}
+
+// Whether the event was queued before the device's stale mark. The events come in order, so the
+// mark is cleared at the first later one and a clock going back can't drop live input for long
+fn is_stale(
+    stale_before: &mut HashMap<PathBuf, SystemTime>,
+    path: &Path,
+    event: &InputEvent,
+) -> bool {
+    match stale_before.get(path) {
+        Some(mark) if event.timestamp() < *mark => true,
+        Some(_) => {
+            stale_before.remove(path);
+            false
+        }
+        None => false,
+    }
+}
+
+// Removes a keyboard that is gone, releasing the keys it held on the virtual keyboard. Returns the
+// end of its tap-hold holds, to handle like other tap-hold output.
+fn remove_keyboard(
+    uinput_device: &mut VirtualDevice,
+    keyboard_states: &mut HashMap<PathBuf, KeyboardState>,
+    tap_hold_state: &mut taphold::TapHolds,
+    path: &Path,
+) -> Vec<(PathBuf, taphold::Output)> {
+    let releases = remove_keyboard_state(keyboard_states, path);
+    if !releases.is_empty() {
+        if let Err(e) = uinput_device.emit(&releases) {
+            log::error!("Unable to release the keys held on {:?}: {}", path, e);
+        }
+    }
+    tap_hold_state.release_holds(Some(path))
+}
+
+// Releases the keys held on the virtual keyboard and the buttons held on the virtual pointers
+fn release_held_keys(
+    uinput_device: &mut VirtualDevice,
+    pointers: &mut HashMap<PathBuf, pointer::Pointer>,
+) {
+    if let Err(e) = uinput::release_all_keys(uinput_device) {
+        log::error!("Unable to release the held keys: {}", e);
+    }
+    for (path, pointer) in pointers.iter_mut() {
+        if let Err(e) = pointer.release_buttons() {
+            log::error!("Unable to release the held buttons of {:?}: {}", path, e);
+        }
+    }
+}
+
+// Exits without leaving anything behind: held keys and buttons are released, the grabbed devices
+// are let go of, and the pidfile and control socket are removed
+fn shutdown(
+    uinput_device: &mut VirtualDevice,
+    keyboard_stream_map: &mut StreamMap<PathBuf, EventStream>,
+    pointers: &mut HashMap<PathBuf, pointer::Pointer>,
+    pidfile: &str,
+    uid: u32,
+    code: i32,
+) -> ! {
+    release_held_keys(uinput_device, pointers);
+    set_keyboards_grabbed(keyboard_stream_map, false);
+    let socket_path = control::socket_path(uid);
+    for path in [Path::new(pidfile), socket_path.as_path()] {
+        match fs::remove_file(path) {
+            // The control socket is missing if it couldn't be created
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                log::error!("Unable to remove {:?}: {}", path, e);
+            }
+            _ => {}
+        }
+    }
+    exit(code);
+}
+
+pub fn set_keyboards_grabbed(
+    keyboard_stream_map: &mut StreamMap<PathBuf, EventStream>,
+    grab: bool,
+) {
+    for (path, stream) in keyboard_stream_map.iter_mut() {
+        let result = if grab { stream.device_mut().grab() } else { stream.device_mut().ungrab() };
+        if let Err(e) = result {
+            log::warn!("Unable to {} {:?}: {}", if grab { "grab" } else { "ungrab" }, path, e);
+        }
+    }
+}
+
+pub fn listen_control_socket(uid: u32) -> std::io::Result<tokio::net::UnixListener> {
+    let socket_path = control::socket_path(uid);
+    if socket_path.exists() {
+        fs::remove_file(&socket_path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(&socket_path)?;
+    fs::set_permissions(&socket_path, fs::Permissions::from_mode(0o600))?;
+    log::debug!("Listening for control requests on {:?}", socket_path);
+    Ok(listener)
+}
+
+async fn read_control_request(
+    stream: &mut tokio::net::UnixStream,
+) -> Result<control::Request, String> {
+    // The socket is only reachable by root, but check the peer anyway
+    match stream.peer_cred() {
+        Ok(cred) if cred.uid() == 0 => {}
+        Ok(cred) => return Err(format!("Permission denied for UID {}", cred.uid())),
+        Err(e) => return Err(format!("Unable to check peer credentials: {}", e)),
+    }
+
+    let mut line = String::new();
+    let mut reader = BufReader::new(stream);
+    match tokio::time::timeout(Duration::from_secs(1), reader.read_line(&mut line)).await {
+        Ok(Ok(_)) => line.parse::<control::Request>(),
+        Ok(Err(e)) => Err(format!("Unable to read request: {}", e)),
+        Err(_) => Err(String::from("Timed out waiting for request")),
+    }
+}
+
+async fn write_control_reply(mut stream: tokio::net::UnixStream, reply: Result<String, String>) {
+    let reply = match reply {
+        Ok(body) => format!("{}\n{}\n", control::REPLY_OK, body),
+        Err(message) => format!("{}\n{}\n", control::REPLY_ERROR, message),
+    };
+    match tokio::time::timeout(Duration::from_secs(1), stream.write_all(reply.as_bytes())).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::warn!("Unable to send control reply: {}", e),
+        Err(_) => log::warn!("Timed out sending control reply"),
+    }
+}
+
 pub fn check_device_is_keyboard(device: &Device) -> bool {
     if device.supported_keys().map_or(false, |keys| keys.contains(Key::KEY_ENTER)) {
-        if device.name() == Some("swhkd virtual output") {
+        if device.name().is_some_and(|name| name.starts_with("swhkd virtual")) {
             return false;
         }
         log::debug!("Keyboard: {}", device.name().unwrap(),);
@@ -381,6 +1458,82 @@ pub fn check_device_is_keyboard(device: &Device) -> bool {
     }
 }
 
+pub fn check_device_is_pointer(device: &Device) -> bool {
+    let has_axes = device
+        .supported_relative_axes()
+        .is_some_and(|axes| axes.contains(evdev::RelativeAxisType::REL_X))
+        || device
+            .supported_absolute_axes()
+            .is_some_and(|axes| axes.contains(evdev::AbsoluteAxisType::ABS_X));
+    if device.supported_keys().is_some_and(|keys| keys.contains(Key::BTN_LEFT)) && has_axes {
+        if device.name() == Some(uinput::POINTER_DEVICE_NAME) {
+            return false;
+        }
+        log::debug!("Pointer: {}", device.name().unwrap_or("unknown"));
+        true
+    } else {
+        false
+    }
+}
+
+// Whether the allow-device and deny-device rules let swhkd grab the device, logging why
+pub fn check_device_is_allowed(
+    filter: &devices::DeviceFilter,
+    path: &Path,
+    device: &Device,
+) -> bool {
+    let info = devices::DeviceInfo::new(path, device);
+    match filter.check(&info) {
+        Ok(reason) => {
+            log::info!("Using {:?} ({}): {}", path, info.name, reason);
+            true
+        }
+        Err(reason) => {
+            log::info!("Ignoring {:?} ({}): {}", path, info.name, reason);
+            false
+        }
+    }
+}
+
+// Loads the config given on the command line, or the default one
+fn load_bindings(args: &ArgMatches) -> Result<config::Bindings, config::Error> {
+    let config_file_path: std::path::PathBuf = if args.is_present("config") {
+        Path::new(args.value_of("config").unwrap()).to_path_buf()
+    } else {
+        fetch_xdg_config_path()
+    };
+
+    log::debug!("Using config file path: {:#?}", config_file_path);
+
+    let layout =
+        args.value_of("layout").map(|layout| layout::Layout::new(layout, args.value_of("variant")));
+    let default_layout = layout::Layout::from_env();
+    config::load_with_layout(&config_file_path, layout.as_ref(), default_layout.as_ref())
+}
+
+// Validates the config for --check, and returns the exit code. Warnings don't fail the check.
+fn check_config(args: &ArgMatches) -> i32 {
+    match load_bindings(args) {
+        Ok(bindings) => {
+            for warning in &bindings.warnings {
+                eprintln!("{}\n", warning);
+            }
+            println!(
+                "Config OK: {} hotkeys, {} remaps and {} tap-hold keys, with {} warning(s).",
+                bindings.hotkeys.len(),
+                bindings.remaps.len(),
+                bindings.tap_holds.len(),
+                bindings.warnings.len()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
 pub fn set_command_line_args() -> Command<'static> {
     let app = Command::new("swhkd")
         .version(env!("CARGO_PKG_VERSION"))
@@ -398,6 +1551,64 @@ pub fn set_command_line_args() -> Command<'static> {
                 .takes_value(true)
                 .help("Set a custom repeat cooldown duration. Default is 250ms."),
         )
+        .arg(
+            arg!(-a --"abort-key" <KEYSYM>)
+                .required(false)
+                .takes_value(true)
+                .help("Set the keysym that aborts chains and modes. Default is Escape."),
+        )
+        .arg(
+            arg!(-t --timeout <TIMEOUT_IN_MS>)
+                .required(false)
+                .takes_value(true)
+                .help("Set a timeout for keychord chains. Default is 0 (no timeout)."),
+        )
+        .arg(
+            arg!(-l --layout <LAYOUT>)
+                .required(false)
+                .takes_value(true)
+                .help("Set the keyboard layout used to resolve keysyms. Default is us."),
+        )
+        .arg(
+            arg!(--variant <VARIANT>)
+                .required(false)
+                .takes_value(true)
+                .requires("layout")
+                .help("Set the variant of the keyboard layout."),
+        )
+        .arg(
+            arg!(-T --"tapping-term" <TAPPING_TERM_IN_MS>)
+                .required(false)
+                .takes_value(true)
+                .help("Set how long tap-hold keys must be held to hold. Default is 200ms."),
+        )
+        .arg(arg!(--"config-error-command" <COMMAND>).required(false).takes_value(true).help(
+            "Run a command through swhks when a reload fails, with the error as its last argument.",
+        ))
+        .arg(
+            arg!(--"allow-device" <MATCHER>)
+                .required(false)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Only grab devices matching one of these, like `name:*Keyboard*` or `id:046d:c52b`."),
+        )
+        .arg(
+            arg!(--"deny-device" <MATCHER>)
+                .required(false)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Never grab devices matching this, like `bus:bluetooth` or `path:*by-id/*Yubico*`."),
+        )
+        .arg(
+            arg!(--"share-state")
+                .required(false)
+                .help("Share held modifiers and keys across keyboards that are in no seat."),
+        )
+        .arg(
+            arg!(--check)
+                .required(false)
+                .help("Check the config and its includes for errors and exit, without root."),
+        )
         .arg(arg!(-d - -debug).required(false).help("Enable debug mode."));
     app
 }
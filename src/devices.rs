@@ -0,0 +1,215 @@
+use crate::config::glob_matches;
+use evdev::Device;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Directories of stable symlinks to the event nodes
+const INPUT_LINK_DIRS: [&str; 2] = ["/dev/input/by-id", "/dev/input/by-path"];
+
+// Bus types from linux/input.h, by the names used in matchers
+const BUS_TYPES: [(&str, u16); 9] = [
+    ("pci", 0x01),
+    ("usb", 0x03),
+    ("bluetooth", 0x05),
+    ("virtual", 0x06),
+    ("i8042", 0x11),
+    ("serial", 0x13),
+    ("i2c", 0x18),
+    ("host", 0x19),
+    ("spi", 0x1c),
+];
+
+// What a device is matched by in `allow-device` and `deny-device`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Matcher {
+    // `name:<glob>`, or a glob without a prefix, matched case insensitively
+    Name(String),
+    // `id:<vendor>:<product>` in hex, with `*` or no product for any product of the vendor
+    Id { vendor: u16, product: Option<u16> },
+    // `bus:usb` or `bus:<hex>`
+    Bus(u16),
+    // `path:<glob>`, matched against the event node and its by-id and by-path symlinks
+    Path(String),
+    // `phys:<glob>`, matched against the physical path the kernel reports
+    Phys(String),
+}
+
+fn parse_hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+impl FromStr for Matcher {
+    type Err = String;
+
+    fn from_str(matcher: &str) -> Result<Self, Self::Err> {
+        let matcher = matcher.trim().trim_matches('"');
+        let invalid = || format!("invalid device matcher `{}`", matcher);
+        let (kind, value) = match matcher.split_once(':') {
+            Some((kind, value))
+                if ["name", "id", "bus", "path", "phys"]
+                    .contains(&kind.to_lowercase().as_str()) =>
+            {
+                (kind.to_lowercase(), value.trim_matches('"'))
+            }
+            _ => (String::from("name"), matcher),
+        };
+        if value.is_empty() {
+            return Err(invalid());
+        }
+        match kind.as_str() {
+            "name" => Ok(Matcher::Name(value.to_string())),
+            "path" => Ok(Matcher::Path(value.to_string())),
+            "phys" => Ok(Matcher::Phys(value.to_string())),
+            "bus" => BUS_TYPES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(value))
+                .map(|(_, bus)| *bus)
+                .or_else(|| parse_hex(value))
+                .map(Matcher::Bus)
+                .ok_or_else(invalid),
+            _ => {
+                let (vendor, product) = match value.split_once(':') {
+                    Some((vendor, "*")) => (vendor, None),
+                    Some((vendor, product)) => {
+                        (vendor, Some(parse_hex(product).ok_or_else(invalid)?))
+                    }
+                    None => (value, None),
+                };
+                Ok(Matcher::Id { vendor: parse_hex(vendor).ok_or_else(invalid)?, product })
+            }
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Matcher::Name(pattern) => write!(f, "name:{}", pattern),
+            Matcher::Id { vendor, product: Some(product) } => {
+                write!(f, "id:{:04x}:{:04x}", vendor, product)
+            }
+            Matcher::Id { vendor, product: None } => write!(f, "id:{:04x}:*", vendor),
+            Matcher::Bus(bus) => match BUS_TYPES.iter().find(|(_, known)| known == bus) {
+                Some((name, _)) => write!(f, "bus:{}", name),
+                None => write!(f, "bus:{:x}", bus),
+            },
+            Matcher::Path(pattern) => write!(f, "path:{}", pattern),
+            Matcher::Phys(pattern) => write!(f, "phys:{}", pattern),
+        }
+    }
+}
+
+// What devices are matched on, read once from the device
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    pub bus: u16,
+    pub phys: String,
+    // The by-id and by-path symlinks to the event node
+    pub links: Vec<PathBuf>,
+}
+
+impl DeviceInfo {
+    pub fn new(path: &Path, device: &Device) -> Self {
+        let id = device.input_id();
+        DeviceInfo {
+            path: path.to_path_buf(),
+            name: device.name().unwrap_or("").to_string(),
+            vendor: id.vendor(),
+            product: id.product(),
+            bus: id.bus_type().0,
+            phys: device.physical_path().unwrap_or("").to_string(),
+            links: links_to(path),
+        }
+    }
+}
+
+fn links_to(path: &Path) -> Vec<PathBuf> {
+    let mut links = Vec::new();
+    for dir in INPUT_LINK_DIRS {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let link = entry.path();
+            if fs::canonicalize(&link).is_ok_and(|target| target == path) {
+                links.push(link);
+            }
+        }
+    }
+    links.sort();
+    links
+}
+
+impl Matcher {
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        match self {
+            Matcher::Name(pattern) => {
+                glob_matches(&pattern.to_lowercase(), &device.name.to_lowercase())
+            }
+            Matcher::Id { vendor, product } => {
+                *vendor == device.vendor && product.iter().all(|product| *product == device.product)
+            }
+            Matcher::Bus(bus) => *bus == device.bus,
+            Matcher::Path(pattern) => std::iter::once(&device.path)
+                .chain(&device.links)
+                .any(|path| glob_matches(pattern, &path.to_string_lossy())),
+            Matcher::Phys(pattern) => glob_matches(pattern, &device.phys),
+        }
+    }
+}
+
+// The devices swhkd may grab, out of those it would grab as keyboards or pointers. With allow
+// rules, only devices matching one of them are grabbed. Deny rules win over allow rules.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceFilter {
+    pub allow: Vec<Matcher>,
+    pub deny: Vec<Matcher>,
+}
+
+impl DeviceFilter {
+    pub fn extend(&mut self, other: DeviceFilter) {
+        self.allow.extend(other.allow);
+        self.deny.extend(other.deny);
+    }
+
+    // Ok with why the device may be grabbed, or Err with why not
+    pub fn check(&self, device: &DeviceInfo) -> Result<String, String> {
+        if let Some(matcher) = self.deny.iter().find(|matcher| matcher.matches(device)) {
+            return Err(format!("matches deny-device {}", matcher));
+        }
+        if self.allow.is_empty() {
+            return Ok(String::from("no allow-device rules"));
+        }
+        match self.allow.iter().find(|matcher| matcher.matches(device)) {
+            Some(matcher) => Ok(format!("matches allow-device {}", matcher)),
+            None => Err(String::from("matches no allow-device rule")),
+        }
+    }
+}
+
+// Devices whose keys are held together, so a modifier held on one of them applies to the keys of
+// the others, like the keyboards of a compositor seat
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seat {
+    pub name: String,
+    // Any device when empty
+    pub devices: Vec<Matcher>,
+}
+
+// The name of the first seat the device is in
+pub fn seat_of<'a>(seats: &'a [Seat], device: &DeviceInfo) -> Option<&'a str> {
+    seats
+        .iter()
+        .find(|seat| {
+            seat.devices.is_empty() || seat.devices.iter().any(|matcher| matcher.matches(device))
+        })
+        .map(|seat| seat.name.as_str())
+}
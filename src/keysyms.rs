@@ -0,0 +1,435 @@
+// Generated by contrib/keysyms/gen_keysyms.py, do not edit.
+// Keysym names are lowercase because the config parser is case insensitive.
+pub const KEYSYMS: &[(&str, u16)] = &[
+    ("escape", 1),
+    ("1", 2),
+    ("exclam", 2),
+    ("2", 3),
+    ("at", 3),
+    ("3", 4),
+    ("numbersign", 4),
+    ("4", 5),
+    ("5", 6),
+    ("percent", 6),
+    ("6", 7),
+    ("asciicircum", 7),
+    ("7", 8),
+    ("ampersand", 8),
+    ("8", 9),
+    ("asterisk", 9),
+    ("9", 10),
+    ("0", 11),
+    ("minus", 12),
+    ("underscore", 12),
+    ("equal", 13),
+    ("plus", 13),
+    ("backspace", 14),
+    ("iso_left_tab", 15),
+    ("tab", 15),
+    ("q", 16),
+    ("w", 17),
+    ("e", 18),
+    ("r", 19),
+    ("t", 20),
+    ("y", 21),
+    ("u", 22),
+    ("i", 23),
+    ("o", 24),
+    ("p", 25),
+    ("braceleft", 26),
+    ("bracketleft", 26),
+    ("braceright", 27),
+    ("bracketright", 27),
+    ("return", 28),
+    ("control_l", 29),
+    ("a", 30),
+    ("s", 31),
+    ("d", 32),
+    ("f", 33),
+    ("g", 34),
+    ("h", 35),
+    ("j", 36),
+    ("k", 37),
+    ("l", 38),
+    ("colon", 39),
+    ("semicolon", 39),
+    ("apostrophe", 40),
+    ("quotedbl", 40),
+    ("asciitilde", 41),
+    ("grave", 41),
+    ("shift_l", 42),
+    ("backslash", 43),
+    ("bar", 43),
+    ("z", 44),
+    ("x", 45),
+    ("c", 46),
+    ("v", 47),
+    ("b", 48),
+    ("n", 49),
+    ("m", 50),
+    ("comma", 51),
+    ("greater", 52),
+    ("period", 52),
+    ("question", 53),
+    ("slash", 53),
+    ("shift_r", 54),
+    ("kp_multiply", 55),
+    ("xf86_cleargrab", 55),
+    ("alt_l", 56),
+    ("meta_l", 56),
+    ("space", 57),
+    ("caps_lock", 58),
+    ("f1", 59),
+    ("xf86_switch_vt_1", 59),
+    ("f2", 60),
+    ("xf86_switch_vt_2", 60),
+    ("f3", 61),
+    ("xf86_switch_vt_3", 61),
+    ("f4", 62),
+    ("xf86_switch_vt_4", 62),
+    ("f5", 63),
+    ("xf86_switch_vt_5", 63),
+    ("f6", 64),
+    ("xf86_switch_vt_6", 64),
+    ("f7", 65),
+    ("xf86_switch_vt_7", 65),
+    ("f8", 66),
+    ("xf86_switch_vt_8", 66),
+    ("f9", 67),
+    ("xf86_switch_vt_9", 67),
+    ("f10", 68),
+    ("xf86_switch_vt_10", 68),
+    ("num_lock", 69),
+    ("scroll_lock", 70),
+    ("kp_7", 71),
+    ("kp_home", 71),
+    ("kp_8", 72),
+    ("kp_up", 72),
+    ("kp_9", 73),
+    ("kp_prior", 73),
+    ("kp_subtract", 74),
+    ("xf86_prev_vmode", 74),
+    ("kp_4", 75),
+    ("kp_left", 75),
+    ("kp_5", 76),
+    ("kp_begin", 76),
+    ("kp_6", 77),
+    ("kp_right", 77),
+    ("kp_add", 78),
+    ("xf86_next_vmode", 78),
+    ("kp_1", 79),
+    ("kp_end", 79),
+    ("kp_2", 80),
+    ("kp_down", 80),
+    ("kp_3", 81),
+    ("kp_next", 81),
+    ("kp_0", 82),
+    ("kp_insert", 82),
+    ("kp_delete", 83),
+    ("iso_level3_shift", 84),
+    ("brokenbar", 86),
+    ("less", 86),
+    ("f11", 87),
+    ("xf86_switch_vt_11", 87),
+    ("f12", 88),
+    ("xf86_switch_vt_12", 88),
+    ("katakana", 90),
+    ("hiragana", 91),
+    ("henkan", 92),
+    ("hiragana_katakana", 93),
+    ("muhenkan", 94),
+    ("kp_enter", 96),
+    ("control_r", 97),
+    ("kp_divide", 98),
+    ("xf86_ungrab", 98),
+    ("print", 99),
+    ("sys_req", 99),
+    ("alt_r", 100),
+    ("meta_r", 100),
+    ("linefeed", 101),
+    ("home", 102),
+    ("up", 103),
+    ("prior", 104),
+    ("left", 105),
+    ("right", 106),
+    ("end", 107),
+    ("down", 108),
+    ("next", 109),
+    ("insert", 110),
+    ("delete", 111),
+    ("xf86audiomute", 113),
+    ("xf86audiolowervolume", 114),
+    ("xf86audioraisevolume", 115),
+    ("xf86poweroff", 116),
+    ("kp_equal", 117),
+    ("plusminus", 118),
+    ("break", 119),
+    ("pause", 119),
+    ("xf86launcha", 120),
+    ("kp_decimal", 121),
+    ("hangul", 122),
+    ("hangul_hanja", 123),
+    ("super_l", 125),
+    ("super_r", 126),
+    ("menu", 127),
+    ("cancel", 128),
+    ("redo", 129),
+    ("sunprops", 130),
+    ("undo", 131),
+    ("sunfront", 132),
+    ("xf86copy", 133),
+    ("xf86open", 134),
+    ("xf86paste", 135),
+    ("find", 136),
+    ("xf86cut", 137),
+    ("help", 138),
+    ("xf86menukb", 139),
+    ("xf86calculator", 140),
+    ("xf86sleep", 142),
+    ("xf86wakeup", 143),
+    ("xf86explorer", 144),
+    ("xf86send", 145),
+    ("xf86xfer", 147),
+    ("xf86launch1", 148),
+    ("xf86launch2", 149),
+    ("xf86www", 150),
+    ("xf86dos", 151),
+    ("xf86screensaver", 152),
+    ("xf86rotatewindows", 153),
+    ("xf86taskpane", 154),
+    ("xf86mail", 155),
+    ("xf86favorites", 156),
+    ("xf86mycomputer", 157),
+    ("xf86back", 158),
+    ("xf86forward", 159),
+    ("xf86eject", 161),
+    ("xf86audionext", 163),
+    ("xf86audioplay", 164),
+    ("xf86audioprev", 165),
+    ("xf86audiostop", 166),
+    ("xf86audiorecord", 167),
+    ("xf86audiorewind", 168),
+    ("xf86phone", 169),
+    ("xf86tools", 171),
+    ("xf86homepage", 172),
+    ("xf86reload", 173),
+    ("xf86close", 174),
+    ("xf86scrollup", 177),
+    ("xf86scrolldown", 178),
+    ("parenleft", 179),
+    ("parenright", 180),
+    ("xf86new", 181),
+    ("xf86launch5", 184),
+    ("xf86launch6", 185),
+    ("xf86launchc", 185),
+    ("xf86launch7", 186),
+    ("xf86launchd", 186),
+    ("xf86launch8", 187),
+    ("xf86launche", 187),
+    ("xf86launch9", 188),
+    ("xf86launchf", 188),
+    ("mode_switch", 195),
+    ("hyper_l", 199),
+    ("xf86audiopause", 201),
+    ("xf86launch3", 202),
+    ("xf86launch4", 203),
+    ("xf86launchb", 204),
+    ("xf86suspend", 205),
+    ("xf86audioforward", 208),
+    ("xf86webcam", 212),
+    ("xf86audiopreset", 213),
+    ("xf86messenger", 216),
+    ("xf86search", 217),
+    ("xf86go", 218),
+    ("xf86finance", 219),
+    ("xf86game", 220),
+    ("xf86shop", 221),
+    ("xf86monbrightnessdown", 224),
+    ("xf86monbrightnessup", 225),
+    ("xf86audiomedia", 226),
+    ("xf86display", 227),
+    ("xf86kbdlightonoff", 228),
+    ("xf86kbdbrightnessdown", 229),
+    ("xf86kbdbrightnessup", 230),
+    ("xf86reply", 232),
+    ("xf86mailforward", 233),
+    ("xf86save", 234),
+    ("xf86documents", 235),
+    ("xf86battery", 236),
+    ("xf86bluetooth", 237),
+    ("xf86wlan", 238),
+    ("xf86uwb", 239),
+    ("xf86next_vmode", 241),
+    ("xf86prev_vmode", 242),
+    ("xf86monbrightnesscycle", 243),
+    ("xf86brightnessauto", 244),
+    ("xf86displayoff", 245),
+    ("xf86wwan", 246),
+    ("xf86rfkill", 247),
+    ("xf86audiomicmute", 248),
+    ("xf86info", 358),
+    ("xf86cycleangle", 371),
+    ("xf86fullscreen", 372),
+    ("xf86keyboard", 374),
+    ("xf86aspectratio", 375),
+    ("xf86dvd", 389),
+    ("xf86audio", 392),
+    ("xf86video", 393),
+    ("xf86calendar", 397),
+    ("xf86channelup", 402),
+    ("xf86channeldown", 403),
+    ("xf86audiorandomplay", 410),
+    ("xf86break", 411),
+    ("xf86videophone", 416),
+    ("xf86zoomin", 418),
+    ("xf86zoomout", 419),
+    ("xf86zoomreset", 420),
+    ("xf86word", 421),
+    ("xf86editor", 422),
+    ("xf86excel", 423),
+    ("xf86graphicseditor", 424),
+    ("xf86presentation", 425),
+    ("xf86database", 426),
+    ("xf86news", 427),
+    ("xf86voicemail", 428),
+    ("xf86addressbook", 429),
+    ("xf86displaytoggle", 431),
+    ("xf86spellcheck", 432),
+    ("xf86logoff", 433),
+    ("dollar", 434),
+    ("eurosign", 435),
+    ("xf86frameback", 436),
+    ("xf86frameforward", 437),
+    ("xf86contextmenu", 438),
+    ("xf86mediarepeat", 439),
+    ("xf8610channelsup", 440),
+    ("xf8610channelsdown", 441),
+    ("xf86images", 442),
+    ("xf86notificationcenter", 444),
+    ("xf86pickupphone", 445),
+    ("xf86hangupphone", 446),
+    ("xf86fn", 464),
+    ("xf86fn_esc", 465),
+    ("xf86fnrightshift", 485),
+    ("braille_dot_1", 497),
+    ("braille_dot_2", 498),
+    ("braille_dot_3", 499),
+    ("braille_dot_4", 500),
+    ("braille_dot_5", 501),
+    ("braille_dot_6", 502),
+    ("braille_dot_7", 503),
+    ("braille_dot_8", 504),
+    ("braille_dot_9", 505),
+    ("xf86numeric0", 512),
+    ("xf86numeric1", 513),
+    ("xf86numeric2", 514),
+    ("xf86numeric3", 515),
+    ("xf86numeric4", 516),
+    ("xf86numeric5", 517),
+    ("xf86numeric6", 518),
+    ("xf86numeric7", 519),
+    ("xf86numeric8", 520),
+    ("xf86numeric9", 521),
+    ("xf86numericstar", 522),
+    ("xf86numericpound", 523),
+    ("xf86numerica", 524),
+    ("xf86numericb", 525),
+    ("xf86numericc", 526),
+    ("xf86numericd", 527),
+    ("xf86camerafocus", 528),
+    ("xf86wpsbutton", 529),
+    ("xf86touchpadtoggle", 530),
+    ("xf86touchpadon", 531),
+    ("xf86touchpadoff", 532),
+    ("xf86camerazoomin", 533),
+    ("xf86camerazoomout", 534),
+    ("xf86cameraup", 535),
+    ("xf86cameradown", 536),
+    ("xf86cameraleft", 537),
+    ("xf86cameraright", 538),
+    ("xf86attendanton", 539),
+    ("xf86attendantoff", 540),
+    ("xf86attendanttoggle", 541),
+    ("xf86lightstoggle", 542),
+    ("xf86alstoggle", 560),
+    ("xf86rotationlocktoggle", 561),
+    ("xf86buttonconfig", 576),
+    ("xf86taskmanager", 577),
+    ("xf86journal", 578),
+    ("xf86controlpanel", 579),
+    ("xf86appselect", 580),
+    ("xf86voicecommand", 582),
+    ("xf86assistant", 583),
+    ("iso_next_group", 584),
+    ("xf86emojipicker", 585),
+    ("xf86brightnessmin", 592),
+    ("xf86brightnessmax", 593),
+    ("xf86kbdinputassistprev", 608),
+    ("xf86kbdinputassistnext", 609),
+    ("xf86kbdinputassistprevgroup", 610),
+    ("xf86kbdinputassistnextgroup", 611),
+    ("xf86kbdinputassistaccept", 612),
+    ("xf86kbdinputassistcancel", 613),
+    ("xf86rightup", 614),
+    ("xf86rightdown", 615),
+    ("xf86leftup", 616),
+    ("xf86leftdown", 617),
+    ("xf86rootmenu", 618),
+    ("xf86mediatopmenu", 619),
+    ("xf86numeric11", 620),
+    ("xf86numeric12", 621),
+    ("xf86audiodesc", 622),
+    ("xf863dmode", 623),
+    ("xf86nextfavorite", 624),
+    ("xf86stoprecord", 625),
+    ("xf86pauserecord", 626),
+    ("xf86vod", 627),
+    ("xf86unmute", 628),
+    ("xf86fastreverse", 629),
+    ("xf86slowreverse", 630),
+    ("xf86data", 631),
+    ("xf86onscreenkeyboard", 632),
+    ("xf86privacyscreentoggle", 633),
+    ("xf86selectivescreenshot", 634),
+    ("xf86macro1", 656),
+    ("xf86macro2", 657),
+    ("xf86macro3", 658),
+    ("xf86macro4", 659),
+    ("xf86macro5", 660),
+    ("xf86macro6", 661),
+    ("xf86macro7", 662),
+    ("xf86macro8", 663),
+    ("xf86macro9", 664),
+    ("xf86macro10", 665),
+    ("xf86macro11", 666),
+    ("xf86macro12", 667),
+    ("xf86macro13", 668),
+    ("xf86macro14", 669),
+    ("xf86macro15", 670),
+    ("xf86macro16", 671),
+    ("xf86macro17", 672),
+    ("xf86macro18", 673),
+    ("xf86macro19", 674),
+    ("xf86macro20", 675),
+    ("xf86macro21", 676),
+    ("xf86macro22", 677),
+    ("xf86macro23", 678),
+    ("xf86macro24", 679),
+    ("xf86macro25", 680),
+    ("xf86macro26", 681),
+    ("xf86macro27", 682),
+    ("xf86macro28", 683),
+    ("xf86macro29", 684),
+    ("xf86macro30", 685),
+    ("xf86macrorecordstart", 688),
+    ("xf86macrorecordstop", 689),
+    ("xf86macropresetcycle", 690),
+    ("xf86macropreset1", 691),
+    ("xf86macropreset2", 692),
+    ("xf86macropreset3", 693),
+    ("xf86kbdlcdmenu1", 696),
+    ("xf86kbdlcdmenu2", 697),
+    ("xf86kbdlcdmenu3", 698),
+    ("xf86kbdlcdmenu4", 699),
+    ("xf86kbdlcdmenu5", 700),
+];
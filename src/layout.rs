@@ -0,0 +1,464 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+// A small reader for the XKB keycodes and symbols files shipped with xkeyboard-config. It
+// resolves keysyms the way a compositor would for `pc105` with the given layout, without
+// linking against libxkbcommon, and only understands what is needed for that: sections,
+// includes and the first group of every key.
+
+pub const DEFAULT_XKB_ROOT: &str = "/usr/share/X11/xkb";
+
+// XKB keycodes are evdev keycodes shifted by 8
+const EVDEV_OFFSET: u32 = 8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Layout {
+    pub name: String,
+    pub variant: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LayoutError(pub String);
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Layout {
+    pub fn new(name: &str, variant: Option<&str>) -> Self {
+        Layout { name: name.to_string(), variant: variant.map(|variant| variant.to_string()) }
+    }
+
+    // Only the first layout of a list like `us,de` is used, like the first XKB group is.
+    pub fn from_env() -> Option<Self> {
+        let name = env::var("XKB_DEFAULT_LAYOUT").ok()?;
+        let name = name.split(',').next()?.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let variant = env::var("XKB_DEFAULT_VARIANT").ok();
+        let variant = variant.as_deref().and_then(|variant| variant.split(',').next());
+        Some(Layout::new(name, variant.filter(|variant| !variant.trim().is_empty())))
+    }
+
+    pub fn xkb_root() -> PathBuf {
+        match env::var("XKB_CONFIG_ROOT") {
+            Ok(root) => PathBuf::from(root),
+            Err(_) => PathBuf::from(DEFAULT_XKB_ROOT),
+        }
+    }
+
+    // Maps every lowercase keysym of the layout to the evdev keycode producing it, and to its shift
+    // level: 0 for none, 1 for shift, 2 for AltGr and 3 for AltGr + shift. Keysyms that need no
+    // modifier win over shifted ones, so both `1` and `exclam` map to KEY_1 on `us`.
+    pub fn keysyms(
+        &self,
+        xkb_root: &Path,
+    ) -> Result<HashMap<String, (evdev::Key, usize)>, LayoutError> {
+        let mut keycodes = HashMap::new();
+        load_keycodes(xkb_root, "evdev", &mut keycodes, 0)?;
+
+        let layout = match &self.variant {
+            Some(variant) => format!("{}({})", self.name, variant),
+            None => self.name.clone(),
+        };
+        let mut symbols = HashMap::new();
+        for name in ["pc", &layout, "inet(evdev)"] {
+            load_symbols(xkb_root, name, &mut symbols, 0)?;
+        }
+
+        let mut keys: Vec<(u32, &Vec<String>)> = symbols
+            .iter()
+            .filter_map(|(key, levels)| Some((*keycodes.get(key)?, levels)))
+            .filter(|(code, _)| *code >= EVDEV_OFFSET)
+            .collect();
+        keys.sort_by_key(|(code, _)| *code);
+
+        let mut keysyms = HashMap::new();
+        let max_level = keys.iter().map(|(_, levels)| levels.len()).max().unwrap_or(0);
+        for level in 0..max_level {
+            for (code, levels) in &keys {
+                let keysym = match levels.get(level) {
+                    Some(keysym) => keysym.to_lowercase(),
+                    None => continue,
+                };
+                if keysym == "nosymbol" || keysym == "voidsymbol" {
+                    continue;
+                }
+                keysyms
+                    .entry(keysym)
+                    .or_insert_with(|| (evdev::Key::new((code - EVDEV_OFFSET) as u16), level));
+            }
+        }
+        Ok(keysyms)
+    }
+}
+
+// Keysym names of the characters that are not their own name, lowercase like the keysym tables
+const CHAR_KEYSYMS: &[(&str, char)] = &[
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("apostrophe", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("minus", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+    ("nobreakspace", '\u{a0}'),
+    ("exclamdown", '¡'),
+    ("cent", '¢'),
+    ("sterling", '£'),
+    ("currency", '¤'),
+    ("yen", '¥'),
+    ("brokenbar", '¦'),
+    ("section", '§'),
+    ("diaeresis", '¨'),
+    ("copyright", '©'),
+    ("ordfeminine", 'ª'),
+    ("guillemotleft", '«'),
+    ("notsign", '¬'),
+    ("registered", '®'),
+    ("macron", '¯'),
+    ("degree", '°'),
+    ("plusminus", '±'),
+    ("twosuperior", '²'),
+    ("threesuperior", '³'),
+    ("acute", '´'),
+    ("mu", 'µ'),
+    ("paragraph", '¶'),
+    ("periodcentered", '·'),
+    ("cedilla", '¸'),
+    ("onesuperior", '¹'),
+    ("masculine", 'º'),
+    ("guillemotright", '»'),
+    ("onequarter", '¼'),
+    ("onehalf", '½'),
+    ("threequarters", '¾'),
+    ("questiondown", '¿'),
+    ("agrave", 'à'),
+    ("aacute", 'á'),
+    ("acircumflex", 'â'),
+    ("atilde", 'ã'),
+    ("adiaeresis", 'ä'),
+    ("aring", 'å'),
+    ("ae", 'æ'),
+    ("ccedilla", 'ç'),
+    ("egrave", 'è'),
+    ("eacute", 'é'),
+    ("ecircumflex", 'ê'),
+    ("ediaeresis", 'ë'),
+    ("igrave", 'ì'),
+    ("iacute", 'í'),
+    ("icircumflex", 'î'),
+    ("idiaeresis", 'ï'),
+    ("eth", 'ð'),
+    ("ntilde", 'ñ'),
+    ("ograve", 'ò'),
+    ("oacute", 'ó'),
+    ("ocircumflex", 'ô'),
+    ("otilde", 'õ'),
+    ("odiaeresis", 'ö'),
+    ("multiply", '×'),
+    ("division", '÷'),
+    ("oslash", 'ø'),
+    ("ugrave", 'ù'),
+    ("uacute", 'ú'),
+    ("ucircumflex", 'û'),
+    ("udiaeresis", 'ü'),
+    ("yacute", 'ý'),
+    ("thorn", 'þ'),
+    ("ssharp", 'ß'),
+    ("ydiaeresis", 'ÿ'),
+    ("eurosign", '€'),
+];
+
+// The character a lowercase keysym name types, if any. Besides the names above, keysyms can be
+// single characters like `a` or `1`, or Unicode code points like `u20ac`.
+pub fn keysym_to_char(keysym: &str) -> Option<char> {
+    let mut chars = keysym.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    if let Some((_, c)) = CHAR_KEYSYMS.iter().find(|(name, _)| *name == keysym) {
+        return Some(*c);
+    }
+    let code_point = keysym.strip_prefix('u').filter(|hex| hex.len() >= 4)?;
+    char::from_u32(u32::from_str_radix(code_point, 16).ok()?)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    Str(String),
+    KeyName(String),
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '#' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '"' => tokens.push(Token::Str(chars.by_ref().take_while(|c| *c != '"').collect())),
+            '<' => tokens.push(Token::KeyName(chars.by_ref().take_while(|c| *c != '>').collect())),
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    word.push(*c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+    tokens
+}
+
+// Returns the tokens of a section, given as `file` for its default section or `file(section)`.
+fn find_section(xkb_root: &Path, kind: &str, name: &str) -> Result<Vec<Token>, LayoutError> {
+    let (file, section) = match name.split_once('(') {
+        Some((file, section)) => (file, Some(section.trim_end_matches(')'))),
+        None => (name, None),
+    };
+    let path = xkb_root.join(kind).join(file);
+    let text = fs::read_to_string(&path)
+        .map_err(|e| LayoutError(format!("Unable to read {:?}: {}", path, e)))?;
+    let tokens = tokenize(&text);
+
+    let mut flags: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Word(word) if word.starts_with("xkb_") => {
+                let section_name = match tokens.get(i + 1) {
+                    Some(Token::Str(section_name)) => section_name.as_str(),
+                    _ => "",
+                };
+                let body_start = i + 3;
+                let body_end = matching_brace(&tokens, i + 2);
+                let is_default = flags.contains(&"default");
+                if section == Some(section_name) || (section.is_none() && is_default) {
+                    return Ok(tokens[body_start..body_end].to_vec());
+                }
+                flags.clear();
+                i = body_end + 1;
+            }
+            Token::Word(word) => {
+                flags.push(word);
+                i += 1;
+            }
+            _ => {
+                flags.clear();
+                i += 1;
+            }
+        }
+    }
+
+    // A file without a `default` section defaults to its first one
+    if section.is_none() {
+        if let Some(start) = tokens.iter().position(|token| token == &Token::Punct('{')) {
+            return Ok(tokens[start + 1..matching_brace(&tokens, start)].to_vec());
+        }
+    }
+    Err(LayoutError(format!("No {} section named {} in {:?}", kind, name, path)))
+}
+
+// Index of the brace closing the one at `open`, or the end of the tokens
+fn matching_brace(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+// Includes can pull several sections at once, like `pc+us(dvorak)`
+fn included_sections(name: &str) -> impl Iterator<Item = &str> {
+    name.split(['+', '|']).filter(|section| !section.is_empty())
+}
+
+// Guards against include loops in broken or malicious XKB data
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+fn check_depth(depth: usize, name: &str) -> Result<(), LayoutError> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(LayoutError(format!("Too many nested includes while loading {}", name)));
+    }
+    Ok(())
+}
+
+fn load_keycodes(
+    xkb_root: &Path,
+    name: &str,
+    keycodes: &mut HashMap<String, u32>,
+    depth: usize,
+) -> Result<(), LayoutError> {
+    check_depth(depth, name)?;
+    let tokens = find_section(xkb_root, "keycodes", name)?;
+    let mut aliases = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match (token, tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3)) {
+            (Token::Word(word), Some(Token::Str(include)), _, _) if word == "include" => {
+                for section in included_sections(include) {
+                    load_keycodes(xkb_root, section, keycodes, depth + 1)?;
+                }
+            }
+            (
+                Token::Word(word),
+                Some(Token::KeyName(alias)),
+                Some(Token::Punct('=')),
+                Some(Token::KeyName(key)),
+            ) if word == "alias" => aliases.push((alias.clone(), key.clone())),
+            (Token::KeyName(key), Some(Token::Punct('=')), Some(Token::Word(code)), _) => {
+                if let Ok(code) = code.parse::<u32>() {
+                    keycodes.insert(key.clone(), code);
+                }
+            }
+            _ => {}
+        }
+    }
+    for (alias, key) in aliases {
+        if let Some(code) = keycodes.get(&key).copied() {
+            keycodes.insert(alias, code);
+        }
+    }
+    Ok(())
+}
+
+fn load_symbols(
+    xkb_root: &Path,
+    name: &str,
+    symbols: &mut HashMap<String, Vec<String>>,
+    depth: usize,
+) -> Result<(), LayoutError> {
+    check_depth(depth, name)?;
+    let tokens = find_section(xkb_root, "symbols", name)?;
+    let mut i = 0;
+    while i < tokens.len() {
+        match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+            (Token::Word(word), Some(Token::Str(include)), _)
+                if ["include", "augment", "override", "replace"].contains(&word.as_str()) =>
+            {
+                for section in included_sections(include) {
+                    load_symbols(xkb_root, section, symbols, depth + 1)?;
+                }
+                i += 2;
+            }
+            (Token::Word(word), Some(Token::KeyName(key)), Some(Token::Punct('{')))
+                if word == "key" =>
+            {
+                let body_end = matching_brace(&tokens, i + 2);
+                if let Some(levels) = first_group(&tokens[i + 3..body_end]) {
+                    symbols.insert(key.clone(), levels);
+                }
+                i = body_end + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    Ok(())
+}
+
+// A key body is either `[ a, A ], [ ... ]` with one list per group, or a list of fields like
+// `type[Group1] = "...", symbols[Group1] = [ a, A ]`.
+fn first_group(body: &[Token]) -> Option<Vec<String>> {
+    let mut i = 0;
+    while i < body.len() {
+        match (&body[i], body.get(i + 1), body.get(i + 2)) {
+            (Token::Word(field), Some(Token::Punct('[')), Some(Token::Word(group))) => {
+                let value_start = i + 5; // field [ group ] =
+                let is_symbols =
+                    field.eq_ignore_ascii_case("symbols") && group.eq_ignore_ascii_case("group1");
+                if is_symbols && body.get(value_start) == Some(&Token::Punct('[')) {
+                    return Some(bracket_list(&body[value_start + 1..]));
+                }
+                i = skip_value(body, value_start);
+            }
+            (Token::Word(_), Some(Token::Punct('=')), _) => i = skip_value(body, i + 2),
+            (Token::Punct('['), _, _) => return Some(bracket_list(&body[i + 1..])),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn bracket_list(tokens: &[Token]) -> Vec<String> {
+    tokens
+        .iter()
+        .take_while(|token| **token != Token::Punct(']'))
+        .filter_map(|token| match token {
+            Token::Word(keysym) => Some(keysym.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Index right after a field value, which is a word, a string or a bracketed list
+fn skip_value(body: &[Token], start: usize) -> usize {
+    if body.get(start) == Some(&Token::Punct('[')) {
+        let mut i = start;
+        while i < body.len() && body[i] != Token::Punct(']') {
+            i += 1;
+        }
+        i + 1
+    } else {
+        start + 1
+    }
+}
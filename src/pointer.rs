@@ -0,0 +1,163 @@
+use crate::config::{SCROLL_DOWN, SCROLL_LEFT, SCROLL_RIGHT, SCROLL_UP};
+use crate::devices::DeviceInfo;
+use crate::uinput;
+use evdev::{
+    uinput::VirtualDevice, AttributeSet, Device, InputEvent, InputEventKind, Key, RelativeAxisType,
+    Synchronization,
+};
+use std::path::Path;
+
+// How a button or scroll step is bound in the current mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Unbound,
+    // Bound, and kept from the applications
+    Swallow,
+    // Bound with `~`, so applications still see it
+    Send,
+}
+
+// A grabbed mouse or touchpad. Its events are replayed a frame at a time on a virtual device with
+// the same capabilities, except for the bound buttons and scroll steps.
+pub struct Pointer {
+    // What the device is, for hotkeys bound to some devices only
+    pub device: DeviceInfo,
+    output: VirtualDevice,
+    frame: Vec<InputEvent>,
+    buttons: Buttons,
+}
+
+// Bound buttons that are held down, and those of them whose press was swallowed, so their
+// release is handled the same way even if the bindings changed in the meantime
+#[derive(Default)]
+pub struct Buttons {
+    pressed: AttributeSet<Key>,
+    swallowed: AttributeSet<Key>,
+}
+
+impl Buttons {
+    pub fn new() -> Self {
+        Buttons::default()
+    }
+}
+
+impl Pointer {
+    pub fn new(path: &Path, device: &Device) -> std::io::Result<Pointer> {
+        Ok(Pointer {
+            device: DeviceInfo::new(path, device),
+            output: uinput::create_pointer_device(device)?,
+            frame: Vec::new(),
+            buttons: Buttons::new(),
+        })
+    }
+
+    // Releases the buttons held on the virtual pointer
+    pub fn release_buttons(&mut self) -> std::io::Result<()> {
+        uinput::release_all_keys(&mut self.output)
+    }
+
+    // Collects the event, and replays its frame once it is complete. Returns the bound buttons
+    // and scroll steps of the frame, as presses (1) and releases (0).
+    pub fn on_event(
+        &mut self,
+        event: InputEvent,
+        binding: impl Fn(Key) -> Binding,
+    ) -> std::io::Result<Vec<(Key, i32)>> {
+        match event.kind() {
+            InputEventKind::Synchronization(Synchronization::SYN_REPORT) => {}
+            InputEventKind::Synchronization(Synchronization::SYN_DROPPED) => {
+                self.frame.clear();
+                return Ok(Vec::new());
+            }
+            InputEventKind::Synchronization(_) => return Ok(Vec::new()),
+            _ => {
+                self.frame.push(event);
+                return Ok(Vec::new());
+            }
+        }
+
+        let frame = std::mem::take(&mut self.frame);
+        let (events, triggered) = filter_frame(frame, &mut self.buttons, binding);
+        if !events.is_empty() {
+            self.output.emit(&events)?;
+        }
+        Ok(triggered)
+    }
+}
+
+// The scroll step pseudo key of a wheel event. High resolution wheel events are mapped as well,
+// so they can be dropped along with the steps they add up to.
+pub fn scroll_key(axis: RelativeAxisType, value: i32) -> Option<Key> {
+    let (positive, negative) = match axis {
+        RelativeAxisType::REL_WHEEL | RelativeAxisType::REL_WHEEL_HI_RES => {
+            (SCROLL_UP, SCROLL_DOWN)
+        }
+        RelativeAxisType::REL_HWHEEL | RelativeAxisType::REL_HWHEEL_HI_RES => {
+            (SCROLL_RIGHT, SCROLL_LEFT)
+        }
+        _ => return None,
+    };
+    match value {
+        0 => None,
+        value if value > 0 => Some(positive),
+        _ => Some(negative),
+    }
+}
+
+// Splits a frame into the events to replay and the bound buttons and scroll steps in it. A scroll
+// step is a press immediately followed by a release.
+pub fn filter_frame(
+    frame: Vec<InputEvent>,
+    buttons: &mut Buttons,
+    binding: impl Fn(Key) -> Binding,
+) -> (Vec<InputEvent>, Vec<(Key, i32)>) {
+    let mut events = Vec::new();
+    let mut triggered = Vec::new();
+
+    for event in frame {
+        match event.kind() {
+            InputEventKind::Key(key) if buttons.pressed.contains(key) => {
+                if event.value() == 0 {
+                    buttons.pressed.remove(key);
+                    triggered.push((key, 0));
+                    if buttons.swallowed.contains(key) {
+                        buttons.swallowed.remove(key);
+                        continue;
+                    }
+                } else if buttons.swallowed.contains(key) {
+                    continue;
+                }
+            }
+            InputEventKind::Key(key) if event.value() == 1 => match binding(key) {
+                Binding::Unbound => {}
+                Binding::Swallow => {
+                    buttons.pressed.insert(key);
+                    buttons.swallowed.insert(key);
+                    triggered.push((key, 1));
+                    continue;
+                }
+                Binding::Send => {
+                    buttons.pressed.insert(key);
+                    triggered.push((key, 1));
+                }
+            },
+            InputEventKind::RelAxis(axis) => {
+                if let Some(key) = scroll_key(axis, event.value()) {
+                    let binding = binding(key);
+                    let step =
+                        matches!(axis, RelativeAxisType::REL_WHEEL | RelativeAxisType::REL_HWHEEL);
+                    if binding != Binding::Unbound && step {
+                        triggered.push((key, 1));
+                        triggered.push((key, 0));
+                    }
+                    if binding == Binding::Swallow {
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+        events.push(event);
+    }
+    (events, triggered)
+}
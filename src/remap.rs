@@ -0,0 +1,98 @@
+use crate::config::{KeyBinding, Modifier, Remap};
+use evdev::{AttributeSet, EventType, InputEvent, Key};
+use std::collections::{HashMap, HashSet};
+
+// What a remap turned a key event into
+#[derive(Debug)]
+pub enum Remapped {
+    // The event is now an event of this key, and is handled like one
+    Key(Key),
+    // The event is replaced by these events, which go straight to the output device
+    Events(Vec<InputEvent>),
+}
+
+// A chord remap whose key is held down, with the modifier keys it had to release and press
+#[derive(Debug)]
+pub struct ActiveRemap {
+    to: KeyBinding,
+    released: Vec<Key>,
+    pressed: Vec<Key>,
+}
+
+impl ActiveRemap {
+    // The keys the remap holds down on the output device
+    pub fn pressed_keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.pressed.iter().copied().chain([self.to.keysym])
+    }
+}
+
+fn key_event(key: Key, value: i32) -> InputEvent {
+    InputEvent::new(EventType::KEY, key.code(), value)
+}
+
+// Remaps one key event. `modifiers` and `modifier_keys_held` are the modifiers currently held on
+// the output device. Modifiers that are not part of a remap are left alone, so with
+// `remap ctrl + h -> backspace`, ctrl + shift + h sends shift + backspace.
+pub fn remap_event(
+    remaps: &[Remap],
+    active_remaps: &mut HashMap<Key, ActiveRemap>,
+    modifiers: &HashSet<Modifier>,
+    modifier_keys_held: &AttributeSet<Key>,
+    key: Key,
+    value: i32,
+) -> Option<Remapped> {
+    // Releases and repeats follow whatever the press was remapped to
+    if let Some(active) = active_remaps.get(&key) {
+        if value != 0 {
+            return Some(Remapped::Events(vec![key_event(active.to.keysym, value)]));
+        }
+        let active = active_remaps.remove(&key).unwrap();
+        let mut events = vec![key_event(active.to.keysym, 0)];
+        events.extend(active.pressed.iter().rev().map(|key| key_event(*key, 0)));
+        events.extend(
+            active
+                .released
+                .iter()
+                .filter(|key| modifier_keys_held.contains(**key))
+                .map(|key| key_event(*key, 1)),
+        );
+        return Some(Remapped::Events(events));
+    }
+
+    let remap = if value == 1 {
+        remaps
+            .iter()
+            .filter(|remap| remap.from.keysym == key)
+            .filter(|remap| {
+                remap.from.modifiers.iter().all(|modifier| modifiers.contains(modifier))
+            })
+            .max_by_key(|remap| remap.from.modifiers.len())?
+    } else {
+        remaps.iter().find(|remap| remap.from.keysym == key && remap.is_key_remap())?
+    };
+
+    if remap.is_key_remap() {
+        return Some(Remapped::Key(remap.to.keysym));
+    }
+
+    let released: Vec<Key> = remap
+        .from
+        .modifiers
+        .iter()
+        .flat_map(|modifier| modifier.keys())
+        .filter(|key| modifier_keys_held.contains(*key))
+        .collect();
+    let pressed: Vec<Key> = remap
+        .to
+        .modifiers
+        .iter()
+        .filter(|modifier| !modifiers.contains(modifier) || remap.from.modifiers.contains(modifier))
+        .map(|modifier| modifier.keys()[0])
+        .collect();
+
+    let mut events: Vec<InputEvent> = released.iter().map(|key| key_event(*key, 0)).collect();
+    events.extend(pressed.iter().map(|key| key_event(*key, 1)));
+    events.push(key_event(remap.to.keysym, 1));
+    active_remaps.insert(key, ActiveRemap { to: remap.to.clone(), released, pressed });
+    Some(Remapped::Events(events))
+}
@@ -0,0 +1,166 @@
+use crate::config::{HoldAction, TapHold};
+use evdev::{EventType, InputEvent, Key};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::time::{Duration, Instant};
+
+// What the daemon should do once tap-hold keys are resolved
+#[derive(Debug)]
+pub enum Output {
+    // Handle this key event as if the keyboard had sent it
+    Key(InputEvent),
+    EnterMode(String),
+    LeaveMode(String),
+}
+
+// A tap-hold key that was pressed but is neither a tap nor a hold yet. Other key events are held
+// back until it is, so they are replayed after the tap or the start of the hold.
+struct Pending {
+    tap_hold: TapHold,
+    path: PathBuf,
+    deadline: Instant,
+    buffered: Vec<(PathBuf, InputEvent)>,
+}
+
+#[derive(Default)]
+pub struct TapHolds {
+    pending: Option<Pending>,
+    // Tap-hold keys currently held as their hold action, and the device they are held on
+    holding: HashMap<Key, (PathBuf, HoldAction)>,
+}
+
+fn key_event(key: Key, value: i32) -> InputEvent {
+    InputEvent::new(EventType::KEY, key.code(), value)
+}
+
+impl TapHolds {
+    pub fn new() -> Self {
+        TapHolds::default()
+    }
+
+    // When the pending key, if any, turns into a hold
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|pending| pending.deadline)
+    }
+
+    pub fn on_event(
+        &mut self,
+        tap_holds: &[TapHold],
+        tapping_term: Duration,
+        path: &Path,
+        event: InputEvent,
+    ) -> Vec<(PathBuf, Output)> {
+        let key = Key::new(event.code());
+
+        if let Some(pending) = &mut self.pending {
+            if key == pending.tap_hold.key {
+                // Auto-repeat of the pending key is dropped, releasing it makes it a tap
+                if event.value() != 0 {
+                    return Vec::new();
+                }
+                let pending = self.pending.take().unwrap();
+                let mut outputs = vec![
+                    (pending.path.clone(), Output::Key(key_event(pending.tap_hold.tap, 1))),
+                    (pending.path, Output::Key(key_event(pending.tap_hold.tap, 0))),
+                ];
+                outputs.extend(
+                    pending.buffered.into_iter().map(|(path, event)| (path, Output::Key(event))),
+                );
+                return outputs;
+            }
+
+            let pressed_while_pending = pending
+                .buffered
+                .iter()
+                .any(|(_, buffered)| buffered.code() == event.code() && buffered.value() == 1);
+            pending.buffered.push((path.to_path_buf(), event));
+            let hold = match event.value() {
+                1 => pending.tap_hold.hold_on_other_key_press,
+                0 => pending.tap_hold.permissive_hold && pressed_while_pending,
+                _ => false,
+            };
+            if hold {
+                return self.start_hold();
+            }
+            return Vec::new();
+        }
+
+        if let Some((_, hold)) = self.holding.get(&key) {
+            return match (hold, event.value()) {
+                (HoldAction::Key(hold_key), 0) => {
+                    let output = Output::Key(key_event(*hold_key, 0));
+                    self.holding.remove(&key);
+                    vec![(path.to_path_buf(), output)]
+                }
+                (HoldAction::Key(hold_key), value) => {
+                    vec![(path.to_path_buf(), Output::Key(key_event(*hold_key, value)))]
+                }
+                (HoldAction::Mode(mode), 0) => {
+                    let output = Output::LeaveMode(mode.clone());
+                    self.holding.remove(&key);
+                    vec![(path.to_path_buf(), output)]
+                }
+                (HoldAction::Mode(_), _) => Vec::new(),
+            };
+        }
+
+        if event.value() == 1 {
+            if let Some(tap_hold) = tap_holds.iter().find(|tap_hold| tap_hold.key == key) {
+                self.pending = Some(Pending {
+                    tap_hold: tap_hold.clone(),
+                    path: path.to_path_buf(),
+                    deadline: Instant::now() + tapping_term,
+                    buffered: Vec::new(),
+                });
+                return Vec::new();
+            }
+        }
+
+        vec![(path.to_path_buf(), Output::Key(event))]
+    }
+
+    // The pending key was held for the whole tapping term
+    pub fn on_timeout(&mut self) -> Vec<(PathBuf, Output)> {
+        if self.pending.is_none() {
+            return Vec::new();
+        }
+        self.start_hold()
+    }
+
+    // Ends the holds on the device at the path, or on every device, whose releases won't come
+    // through here: the device is gone, or swhkd is paused and lets its events through as they are
+    pub fn release_holds(&mut self, path: Option<&Path>) -> Vec<(PathBuf, Output)> {
+        let keys: Vec<Key> = self
+            .holding
+            .iter()
+            .filter(|(_, (held_on, _))| path.is_none_or(|path| held_on == path))
+            .map(|(key, _)| *key)
+            .collect();
+        keys.into_iter()
+            .map(|key| {
+                let (held_on, hold) = self.holding.remove(&key).unwrap();
+                let output = match hold {
+                    HoldAction::Key(hold_key) => Output::Key(key_event(hold_key, 0)),
+                    HoldAction::Mode(mode) => Output::LeaveMode(mode),
+                };
+                (held_on, output)
+            })
+            .collect()
+    }
+
+    fn start_hold(&mut self) -> Vec<(PathBuf, Output)> {
+        let pending = self.pending.take().unwrap();
+        let output = match &pending.tap_hold.hold {
+            HoldAction::Key(hold_key) => Output::Key(key_event(*hold_key, 1)),
+            HoldAction::Mode(mode) => Output::EnterMode(mode.clone()),
+        };
+        self.holding.insert(pending.tap_hold.key, (pending.path.clone(), pending.tap_hold.hold));
+
+        let mut outputs = vec![(pending.path, output)];
+        outputs
+            .extend(pending.buffered.into_iter().map(|(path, event)| (path, Output::Key(event))));
+        outputs
+    }
+}
@@ -1,8 +1,11 @@
 mod test_config {
     use crate::config::{
-        extract_curly_brace, load, load_file_contents, parse_contents, Error, Hotkey, Modifier,
-        ParseError, Prefix,
+        extract_curly_brace, key_to_evdev_key, load, load_file_contents, load_with_layout,
+        parse_contents, Config, Error, Hotkey, KeyStroke, ModeAction, Modifier, ParseError, Prefix,
     };
+    use crate::devices::{DeviceInfo, Matcher, Seat};
+    use crate::{check_config, set_command_line_args};
+    use std::collections::HashMap;
     use std::fs;
     use std::io::Write;
     use std::{fs::File, path::PathBuf};
@@ -86,7 +89,7 @@ mod test_config {
 
         // Check if the Error type is InvalidConfig
         let result = match result {
-            Error::InvalidConfig(parse_err) => parse_err,
+            Error::InvalidConfig(diagnostics) => diagnostics[0].error.clone(),
             _ => panic!(),
         };
 
@@ -163,6 +166,30 @@ super + c
         Ok(())
     }
 
+    #[test]
+    fn test_load_paths() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-paths1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"
+include /tmp/swhkd-test-paths2
+super + b
+   firefox",
+        )?;
+
+        let setup2 = TestPath::new("/tmp/swhkd-test-paths2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"
+super + c
+    hello",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert_eq!(bindings.paths, vec![setup.path(), setup2.path()]);
+        Ok(())
+    }
+
     #[test]
     fn test_relative_import() -> std::io::Result<()> {
         let setup = TestPath::new("/tmp/swhkd-relative-file1");
@@ -174,7 +201,7 @@ super + b
    firefox",
         )?;
 
-        let setup2 = TestPath::new("swhkd-relative-file2");
+        let setup2 = TestPath::new("/tmp/swhkd-relative-file2");
         let mut f2 = File::create(setup2.path())?;
         f2.write_all(
             b"
@@ -354,830 +381,3030 @@ shift + alt +
     }
 
     #[test]
-    fn test_common_modifiers() -> std::io::Result<()> {
+    fn test_all_errors_reported() {
         let contents = "
-shift + k
-    notify-send 'Hello world!'
-
-control + 5
-    notify-send 'Hello world!'
-
-alt + 2
-    notify-send 'Hello world!'
-
-super + z
-    notify-send 'Hello world!'
-            ";
-
-        let expected_hotkeys = vec![
-            Hotkey::new(
-                evdev::Key::KEY_K,
-                vec![Modifier::Shift],
-                "notify-send 'Hello world!'".to_string(),
-            ),
-            Hotkey::new(
-                evdev::Key::KEY_5,
-                vec![Modifier::Control],
-                "notify-send 'Hello world!'".to_string(),
-            ),
-            Hotkey::new(
-                evdev::Key::KEY_2,
-                vec![Modifier::Alt],
-                "notify-send 'Hello world!'".to_string(),
-            ),
-            Hotkey::new(
-                evdev::Key::KEY_Z,
-                vec![Modifier::Super],
-                "notify-send 'Hello world!'".to_string(),
-            ),
-        ];
-
-        eval_config_test(contents, expected_hotkeys)
+supr + a
+    notify-send a
+super + b
+    notify-send b
+super + {c, retrun}
+    notify-send {c, d}
+super + shift
+    notify-send e";
+
+        let diagnostics = match parse_contents(PathBuf::new(), contents.to_string()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        let found: Vec<(ParseError, &str, std::ops::Range<usize>, Option<&str>)> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.error.clone(),
+                    diagnostic.token.as_str(),
+                    diagnostic.columns.clone(),
+                    diagnostic.suggestion.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (ParseError::UnknownSymbol(PathBuf::new(), 2), "supr", 0..4, Some("super")),
+                (ParseError::UnknownSymbol(PathBuf::new(), 6), "retrun", 12..18, Some("return")),
+                (ParseError::InvalidKeysym(PathBuf::new(), 8), "shift", 8..13, None),
+            ]
+        );
+        assert_eq!(diagnostics[1].source_line, "super + {c, retrun}");
     }
 
     #[test]
-    fn test_command_with_many_spaces() -> std::io::Result<()> {
+    fn test_error_columns_of_repeated_tokens() {
         let contents = "
-p
-    xbacklight -inc 10 -fps 30 -time 200
-        ";
-
-        let expected_keybinds = vec![Hotkey::new(
-            evdev::Key::KEY_P,
-            vec![],
-            String::from("xbacklight -inc 10 -fps 30 -time 200"),
-        )];
+alt + a + b
+    notify-send a
+super + shift + shift
+    notify-send b
+alt + x ; a + b
+    notify-send c
+super + {c, alt}
+    notify-send {c, d}";
+
+        let diagnostics = match parse_contents(PathBuf::new(), contents.to_string()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        let found: Vec<(ParseError, &str, std::ops::Range<usize>)> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (diagnostic.error.clone(), diagnostic.token.as_str(), diagnostic.columns.clone())
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                // Not the `a` in `alt`
+                (ParseError::InvalidModifier(PathBuf::new(), 2), "a", 6..7),
+                // Not the first `shift`, which is a valid modifier
+                (ParseError::InvalidKeysym(PathBuf::new(), 4), "shift", 16..21),
+                (ParseError::InvalidModifier(PathBuf::new(), 6), "a", 10..11),
+                // Expanded by curly braces, so found by its token
+                (ParseError::InvalidKeysym(PathBuf::new(), 8), "alt", 12..15),
+            ]
+        );
+        assert_eq!(diagnostics[0].source_line, "alt + a + b");
+    }
 
-        eval_config_test(contents, expected_keybinds)
+    // Runs `swhkd --check` on the config, and returns its exit code
+    fn check(name: &str, contents: &str) -> std::io::Result<i32> {
+        let setup = TestPath::new(&format!("/tmp/swhkd-test-check-{}", name));
+        fs::write(setup.path(), contents)?;
+        let args = set_command_line_args().get_matches_from([
+            "swhkd",
+            "--check",
+            "--config",
+            setup.path().to_str().unwrap(),
+        ]);
+        Ok(check_config(&args))
     }
 
     #[test]
-    fn test_invalid_keybinding() -> std::io::Result<()> {
-        let contents = "
-p
-    xbacklight -inc 10 -fps 30 -time 200
+    fn test_check_blank_hotkey() -> std::io::Result<()> {
+        assert_eq!(check("hotkey", "_\n    notify-send a\n")?, 1);
+        assert_eq!(check("hotkey-chord", "_ + _\n    notify-send a\n")?, 1);
+        Ok(())
+    }
 
-pesto
-    xterm
-                    ";
+    #[test]
+    fn test_check_blank_remap() -> std::io::Result<()> {
+        assert_eq!(check("remap", "remap _ -> a\n")?, 1);
+        Ok(())
+    }
 
-        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 5))
+    #[test]
+    fn test_check_blank_keys_action() -> std::io::Result<()> {
+        assert_eq!(check("keys", "super + a\n    @keys _\n")?, 1);
+        Ok(())
     }
 
     #[test]
-    // keysyms not followed by command should be ignored
-    fn test_no_command() -> std::io::Result<()> {
+    fn test_blank_keybinding_error() {
         let contents = "
-k
-    xbacklight -inc 10 -fps 30 -time 200
+super + a
+    @keys ctrl+c _";
 
-w
+        let diagnostics = match parse_contents(PathBuf::new(), contents.to_string()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].error, ParseError::UnknownSymbol(PathBuf::new(), 3));
+        assert_eq!(diagnostics[0].token, "_");
+        assert_eq!(diagnostics[0].columns, 17..18);
+    }
 
-                    ";
+    #[test]
+    fn test_errors_reported_across_files() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-errors1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"remap capslock escape
+taphold space space ctrl permisive-hold
+super + q
+    @type \"\x01\"",
+        )?;
 
-        eval_config_test(
-            contents,
-            vec![Hotkey::new(
-                evdev::Key::KEY_K,
-                vec![],
-                "xbacklight -inc 10 -fps 30 -time 200".to_string(),
-            )],
-        )
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        let found: Vec<(u32, &str)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.error.line_nr(), diagnostic.token.as_str()))
+            .collect();
+        assert_eq!(found, vec![(1, "capslock escape"), (2, "permisive-hold"), (4, "\x01")]);
+        Ok(())
     }
 
     #[test]
-    fn test_real_config_snippet() -> std::io::Result<()> {
-        let contents = "
-# reloads sxhkd configuration:
-super + Escape
-    pkill -USR1 -x sxhkd ; sxhkd &
-
-# Launch Terminal
-super + Return
-    alacritty -t \"Terminal\" -e \"$HOME/.config/sxhkd/new_tmux_terminal.sh\"
+    fn test_errors_attributed_to_their_file() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-attribution1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"include /tmp/swhkd-test-attribution2
+supr + a
+    a",
+        )?;
 
-# terminal emulator (no tmux)
-super + shift + Return
-    alacritty -t \"Terminal\"
+        let setup2 = TestPath::new("/tmp/swhkd-test-attribution2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"
+remap capslock -> nope
+super + b
+    b",
+        )?;
 
-# terminal emulator (new tmux session)
-alt + Return
-    alacritty -t \"Terminal\" -e \"tmux\"
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        let found: Vec<ParseError> =
+            diagnostics.into_iter().map(|diagnostic| diagnostic.error).collect();
+        assert_eq!(
+            found,
+            vec![
+                ParseError::UnknownSymbol(setup.path(), 2),
+                ParseError::UnknownSymbol(setup2.path(), 2),
+            ]
+        );
+        Ok(())
+    }
 
-ctrl + 0
-    play-song.sh
+    #[test]
+    fn test_hotkey_origin() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-origin1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"include /tmp/swhkd-test-origin2
 
-super + minus
-    play-song.sh album
-                    ";
+super + a
+    a",
+        )?;
 
-        let expected_result: Vec<Hotkey> = vec![
-            Hotkey::new(
-                evdev::Key::KEY_ESC,
-                vec![Modifier::Super],
-                String::from("pkill -USR1 -x sxhkd ; sxhkd &"),
-            ),
-            Hotkey::new(
-                evdev::Key::KEY_ENTER,
-                vec![Modifier::Super],
-                String::from(
-                    "alacritty -t \"Terminal\" -e \"$HOME/.config/sxhkd/new_tmux_terminal.sh\"",
-                ),
-            ),
-            Hotkey::new(
-                evdev::Key::KEY_ENTER,
-                vec![Modifier::Super, Modifier::Shift],
-                String::from("alacritty -t \"Terminal\""),
-            ),
-            Hotkey::new(
-                evdev::Key::KEY_ENTER,
-                vec![Modifier::Alt],
-                String::from("alacritty -t \"Terminal\" -e \"tmux\""),
-            ),
-            Hotkey::new(evdev::Key::KEY_0, vec![Modifier::Control], String::from("play-song.sh")),
-            Hotkey::new(
-                evdev::Key::KEY_MINUS,
-                vec![Modifier::Super],
-                String::from("play-song.sh album"),
-            ),
-        ];
+        let setup2 = TestPath::new("/tmp/swhkd-test-origin2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"# chains
+super + b; c
+    c",
+        )?;
 
-        eval_config_test(contents, expected_result)
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        let origins: Vec<String> = bindings
+            .hotkeys
+            .iter()
+            .map(|hotkey| hotkey.origin.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                "/tmp/swhkd-test-origin1:3",
+                "/tmp/swhkd-test-origin2:2",
+                "/tmp/swhkd-test-origin2:2",
+            ]
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_multiline_command() -> std::io::Result<()> {
-        let contents = "
-k
-    mpc ls | dmenu | \\
-    sed -i 's/foo/bar/g'
-                    ";
+    fn test_missing_include() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-include1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"super + b
+    firefox
+include /tmp/swhkd-test-include2",
+        )?;
 
-        let expected_keybind = Hotkey::new(
-            evdev::Key::KEY_K,
-            vec![],
-            String::from("mpc ls | dmenu | sed -i 's/foo/bar/g'"),
-        );
+        let setup2 = TestPath::new("/tmp/swhkd-test-include2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"
+include /tmp/swhkd-test-nonexistent",
+        )?;
 
-        eval_config_test(contents, vec![expected_keybind])
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].error, ParseError::MissingInclude(setup2.path(), 2));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error: included file not found `/tmp/swhkd-test-nonexistent`
+ --> /tmp/swhkd-test-include2:2:9
+  |
+2 | include /tmp/swhkd-test-nonexistent
+  |         ^^^^^^^^^^^^^^^^^^^^^^^^^^^"
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_commented_out_keybind() -> std::io::Result<()> {
-        let contents = "
-#w
-    gimp
-                    ";
+    fn test_include_path_expansion() {
+        std::env::set_var("SWHKD_TEST_INCLUDE_DIR", "/opt/swhkd");
+        let home = nix::unistd::User::from_uid(nix::unistd::geteuid()).unwrap().unwrap().dir;
+        let contents = "include ~/work.rc
+include $SWHKD_TEST_INCLUDE_DIR/a.rc
+include \"${SWHKD_TEST_INCLUDE_DIR}/with space.rc\"
+include '$SWHKD_TEST_INCLUDE_DIR/b.rc'
+include ./relative/c.rc
+include ../d.rc
+include /etc/swhkd/e.rc";
+
+        let (imports, invalid_imports) =
+            Config::get_imports(&PathBuf::from("/home/user/.config/swhkd/swhkdrc"), contents);
+        assert_eq!(invalid_imports, vec![]);
+        let paths: Vec<PathBuf> = imports.into_iter().map(|import| import.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                home.join("work.rc"),
+                PathBuf::from("/opt/swhkd/a.rc"),
+                PathBuf::from("/opt/swhkd/with space.rc"),
+                PathBuf::from("/home/user/.config/swhkd/$SWHKD_TEST_INCLUDE_DIR/b.rc"),
+                PathBuf::from("/home/user/.config/swhkd/relative/c.rc"),
+                PathBuf::from("/home/user/.config/swhkd/../d.rc"),
+                PathBuf::from("/etc/swhkd/e.rc"),
+            ]
+        );
+    }
 
-        eval_config_test(contents, vec![])
+    #[test]
+    fn test_invalid_include_paths() {
+        let path = PathBuf::from("/etc/swhkd/swhkdrc");
+        let contents = "include $SWHKD_TEST_UNDEFINED/a.rc
+include \"/etc/swhkd/b.rc";
+
+        let (imports, invalid_imports) = Config::get_imports(&path, contents);
+        assert_eq!(imports, vec![]);
+        let found: Vec<(ParseError, &str)> = invalid_imports
+            .iter()
+            .map(|diagnostic| (diagnostic.error.clone(), diagnostic.token.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (ParseError::UndefinedVariable(path.clone(), 1), "$SWHKD_TEST_UNDEFINED"),
+                (ParseError::UnterminatedQuote(path.clone(), 2), "\"/etc/swhkd/b.rc"),
+            ]
+        );
     }
 
-    // TODO: Write these tests as needed.
+    #[test]
+    fn test_glob_and_directory_includes() -> std::io::Result<()> {
+        let dir = PathBuf::from("/tmp/swhkd-test-glob");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("swhkdrc.d"))?;
+        fs::create_dir_all(dir.join("machines/laptop"))?;
+        fs::create_dir_all(dir.join("machines/desktop"))?;
+        fs::write(
+            dir.join("swhkdrc"),
+            "include swhkdrc.d
+include machines/*/[a-z]*.rc
+include nothing/*.rc
+a
+    a",
+        )?;
+        fs::write(dir.join("swhkdrc.d/20-b"), "b\n    b")?;
+        fs::write(dir.join("swhkdrc.d/10-c"), "c\n    c")?;
+        fs::write(dir.join("swhkdrc.d/.hidden"), "h\n    h")?;
+        fs::write(dir.join("machines/laptop/d.rc"), "d\n    d")?;
+        fs::write(dir.join("machines/desktop/e.rc"), "e\n    e")?;
+        fs::write(dir.join("machines/desktop/1.rc"), "f\n    f")?;
+        fs::write(dir.join("machines/desktop/e.rc~"), "g\n    g")?;
+
+        let bindings = load_with_layout(&dir.join("swhkdrc"), None, None);
+        fs::remove_dir_all(&dir)?;
+        let bindings = bindings.unwrap();
+
+        let commands: Vec<&str> =
+            bindings.hotkeys.iter().map(|hotkey| hotkey.command.as_str()).collect();
+        assert_eq!(commands, vec!["a", "c", "b", "e", "d"]);
+        assert_eq!(
+            bindings.dirs,
+            vec![
+                dir.join("swhkdrc.d"),
+                dir.join("machines"),
+                dir.join("machines/desktop"),
+                dir.join("machines/laptop"),
+            ]
+        );
+        Ok(())
+    }
 
     #[test]
-    fn test_all_alphanumeric() -> std::io::Result<()> {
-        let symbols: [&str; 36] = [
-            "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q",
-            "r", "s", "t", "u", "v", "w", "x", "y", "z", "0", "1", "2", "3", "4", "5", "6", "7",
-            "8", "9",
-        ];
-        let keysyms: [evdev::Key; 36] = [
-            evdev::Key::KEY_A,
-            evdev::Key::KEY_B,
-            evdev::Key::KEY_C,
-            evdev::Key::KEY_D,
-            evdev::Key::KEY_E,
-            evdev::Key::KEY_F,
-            evdev::Key::KEY_G,
-            evdev::Key::KEY_H,
-            evdev::Key::KEY_I,
-            evdev::Key::KEY_J,
-            evdev::Key::KEY_K,
-            evdev::Key::KEY_L,
-            evdev::Key::KEY_M,
-            evdev::Key::KEY_N,
-            evdev::Key::KEY_O,
-            evdev::Key::KEY_P,
-            evdev::Key::KEY_Q,
-            evdev::Key::KEY_R,
-            evdev::Key::KEY_S,
-            evdev::Key::KEY_T,
-            evdev::Key::KEY_U,
-            evdev::Key::KEY_V,
-            evdev::Key::KEY_W,
-            evdev::Key::KEY_X,
-            evdev::Key::KEY_Y,
-            evdev::Key::KEY_Z,
-            evdev::Key::KEY_0,
-            evdev::Key::KEY_1,
-            evdev::Key::KEY_2,
-            evdev::Key::KEY_3,
-            evdev::Key::KEY_4,
-            evdev::Key::KEY_5,
-            evdev::Key::KEY_6,
-            evdev::Key::KEY_7,
-            evdev::Key::KEY_8,
-            evdev::Key::KEY_9,
+    fn test_include_cycle() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-cycle1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"include /tmp/swhkd-test-cycle2
+include /tmp/swhkd-test-cycle3
+a
+    a",
+        )?;
+
+        let setup2 = TestPath::new("/tmp/swhkd-test-cycle2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"include /tmp/swhkd-test-cycle3
+b
+    b",
+        )?;
+
+        let setup3 = TestPath::new("/tmp/swhkd-test-cycle3");
+        let mut f3 = File::create(setup3.path())?;
+        f3.write_all(
+            b"c
+    c
+include /tmp/swhkd-test-cycle1",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert_eq!(bindings.hotkeys.len(), 3);
+        let found: Vec<String> =
+            bindings.warnings.iter().map(|warning| warning.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "warning: include cycle /tmp/swhkd-test-cycle1 -> /tmp/swhkd-test-cycle2 -> \
+                 /tmp/swhkd-test-cycle3 -> /tmp/swhkd-test-cycle1, each file is only loaded once
+ --> /tmp/swhkd-test-cycle3:3"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_warnings() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-warnings1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"include /tmp/swhkd-test-warnings2
+super + a
+    notify-send a
+super + a
+    notify-send b
+super + b
+    notify-send b
+super + b; c
+    notify-send c",
+        )?;
+
+        let setup2 = TestPath::new("/tmp/swhkd-test-warnings2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"super + c
+    notify-send c
+super + a
+    notify-send other",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        let found: Vec<(PathBuf, Option<u32>, &str)> = bindings
+            .warnings
+            .iter()
+            .map(|warning| (warning.path.clone(), warning.line_nr, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    setup.path(),
+                    Some(4),
+                    "`super + KEY_A` is shadowed by the binding at /tmp/swhkd-test-warnings1:2"
+                ),
+                (
+                    setup.path(),
+                    Some(8),
+                    "the keychord chain starting with `super + KEY_B` can never be entered, it is \
+                     shadowed by the binding at /tmp/swhkd-test-warnings1:6"
+                ),
+                (
+                    setup2.path(),
+                    Some(3),
+                    "`super + KEY_A` is shadowed by the binding at /tmp/swhkd-test-warnings1:2"
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_precedence_last() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-precedence1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"precedence last
+include /tmp/swhkd-test-precedence2
+super + a
+    mine-a
+override super + c
+    mine-c",
+        )?;
+
+        let setup2 = TestPath::new("/tmp/swhkd-test-precedence2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"super + a
+    team-a
+super + b; x
+    team-chain
+super + c
+    team-c
+super + b
+    team-b",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        let commands: Vec<&str> =
+            bindings.hotkeys.iter().map(|hotkey| hotkey.command.as_str()).collect();
+        assert_eq!(commands, vec!["mine-a", "mine-c", "team-b"]);
+
+        let found: Vec<String> =
+            bindings.warnings.iter().map(|warning| warning.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "warning: the keychord chain starting with `super + KEY_B` can never be entered, \
+                 it is shadowed by the binding at /tmp/swhkd-test-precedence2:7
+ --> /tmp/swhkd-test-precedence2:3",
+                "warning: `super + KEY_A` is shadowed by the binding at /tmp/swhkd-test-precedence1:3
+ --> /tmp/swhkd-test-precedence2:1",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_override() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-override1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"include /tmp/swhkd-test-override2
+super + {a, b}
+    mine-{a, b}",
+        )?;
+
+        let setup2 = TestPath::new("/tmp/swhkd-test-override2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"override super + {a, c}
+    system-{a, c}",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        let commands: Vec<&str> =
+            bindings.hotkeys.iter().map(|hotkey| hotkey.command.as_str()).collect();
+        assert_eq!(commands, vec!["system-a", "mine-b", "system-c"]);
+        assert_eq!(bindings.warnings, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbind() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-unbind1");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"unbind super + {q, w}
+include /tmp/swhkd-test-unbind2
+unbind super + x; y
+mode music
+unbind n
+endmode
+unbind super + z",
+        )?;
+
+        let setup2 = TestPath::new("/tmp/swhkd-test-unbind2");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(
+            b"super + {q, w, e}
+    team-{q, w, e}
+super + x; y
+    team-chain
+mode music
+n
+    mpc next
+p
+    mpc prev
+endmode
+n
+    kept",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        let commands: Vec<&str> =
+            bindings.hotkeys.iter().map(|hotkey| hotkey.command.as_str()).collect();
+        assert_eq!(commands, vec!["team-e", "mpc prev", "kept"]);
+
+        let found: Vec<String> =
+            bindings.warnings.iter().map(|warning| warning.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "warning: `super + KEY_Z` is not bound, so there is nothing to unbind
+ --> /tmp/swhkd-test-unbind1:7"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_unbind() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-unbind3");
+        let mut f = File::create(setup.path())?;
+        f.write_all(b"unbind super + nope")?;
+
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics[0].error, ParseError::UnknownSymbol(setup.path(), 1));
+        assert_eq!(diagnostics[0].token, "nope");
+        Ok(())
+    }
+
+    #[test]
+    fn test_device_rules() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-devices");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"allow-device *Keyboard*
+deny-device id:1050:*
+deny-device bus:bluetooth
+super + k
+    notify-send k",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert_eq!(bindings.hotkeys.len(), 1);
+        assert_eq!(bindings.devices.allow, vec![Matcher::Name(String::from("*Keyboard*"))]);
+        assert_eq!(
+            bindings.devices.deny,
+            vec![Matcher::Id { vendor: 0x1050, product: None }, Matcher::Bus(0x05)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_device_rule() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-devices2");
+        let mut f = File::create(setup.path())?;
+        f.write_all(b"deny-device id:yubikey")?;
+
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics[0].error, ParseError::UnknownSymbol(setup.path(), 1));
+        assert_eq!(diagnostics[0].token, "id:yubikey");
+        Ok(())
+    }
+
+    #[test]
+    fn test_device_sections() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-device-sections");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"super + 1
+    notify-send any
+[device \"Macro Pad\", id:1234:*]
+super + 1
+    notify-send pad
+super + 2
+    notify-send pad2
+unbind super + 2
+[device]
+super + 3
+    notify-send any3",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert!(bindings.warnings.is_empty(), "{:?}", bindings.warnings);
+        let pad = vec![
+            Matcher::Name(String::from("Macro Pad")),
+            Matcher::Id { vendor: 0x1234, product: None },
         ];
+        assert_eq!(
+            bindings.hotkeys,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super],
+                    "notify-send any".to_string()
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super],
+                    "notify-send pad".to_string()
+                )
+                .on_devices(pad),
+                Hotkey::new(
+                    evdev::Key::KEY_3,
+                    vec![Modifier::Super],
+                    "notify-send any3".to_string()
+                ),
+            ]
+        );
+
+        let macro_pad = DeviceInfo { name: String::from("Macro Pad"), ..DeviceInfo::default() };
+        let laptop = DeviceInfo {
+            name: String::from("AT Translated Set 2 keyboard"),
+            ..DeviceInfo::default()
+        };
+        assert!(bindings.hotkeys[1].triggers_on(&macro_pad));
+        assert!(!bindings.hotkeys[1].triggers_on(&laptop));
+        assert!(bindings.hotkeys[2].triggers_on(&laptop));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_device_sections() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-device-sections2");
+        let mut f = File::create(setup.path())?;
+        f.write_all(b"[devices \"Macro Pad\"]\n[device bus:firewire]\n[device")?;
+
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].error, ParseError::UnknownSymbol(setup.path(), 1));
+        assert_eq!(diagnostics[0].suggestion, Some(String::from("device")));
+        assert_eq!(diagnostics[1].error, ParseError::UnknownSymbol(setup.path(), 2));
+        assert_eq!(diagnostics[1].token, "bus:firewire");
+        assert_eq!(diagnostics[2].error, ParseError::UnknownSymbol(setup.path(), 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bracket_key_is_not_a_section() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-device-sections3");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"[device \"Macro Pad\"]
+[
+    notify-send pad
+[device]
+[
+    notify-send any",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert!(bindings.warnings.is_empty(), "{:?}", bindings.warnings);
+        let keys: Vec<(evdev::Key, &str, bool)> = bindings
+            .hotkeys
+            .iter()
+            .map(|hotkey| {
+                (hotkey.keybinding.keysym, hotkey.command.as_str(), hotkey.devices.is_empty())
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                (evdev::Key::KEY_LEFTBRACE, "notify-send pad", false),
+                (evdev::Key::KEY_LEFTBRACE, "notify-send any", true),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_seats() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-seats");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"seat desk id:046d:*, \"Foot Pedal\"\nseat laptop\nseat\nseat broken id:logitech",
+        )?;
+
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].error, ParseError::UnknownSymbol(setup.path(), 3));
+        assert_eq!(diagnostics[1].error, ParseError::UnknownSymbol(setup.path(), 4));
+        assert_eq!(diagnostics[1].token, "id:logitech");
+
+        fs::write(setup.path(), "seat desk id:046d:*, \"Foot Pedal\"\nseat laptop")?;
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert_eq!(
+            bindings.seats,
+            vec![
+                Seat {
+                    name: String::from("desk"),
+                    devices: vec![
+                        Matcher::Id { vendor: 0x046d, product: None },
+                        Matcher::Name(String::from("Foot Pedal")),
+                    ],
+                },
+                Seat { name: String::from("laptop"), devices: Vec::new() },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_included_precedence() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-precedence4");
+        let mut f = File::create(setup.path())?;
+        f.write_all(b"include /tmp/swhkd-test-precedence5\nsuper + a\n    a")?;
+        let setup2 = TestPath::new("/tmp/swhkd-test-precedence5");
+        let mut f2 = File::create(setup2.path())?;
+        f2.write_all(b"super + a\n    b\nprecedence last")?;
+
+        // Ignored with a warning, so the main config's binding still wins
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert_eq!(bindings.hotkeys.len(), 1);
+        assert_eq!(bindings.hotkeys[0].command, "a");
+        assert!(bindings.warnings.iter().any(|warning| warning.path == setup2.path()
+            && warning.line_nr == Some(3)
+            && warning.message.contains("`precedence` only applies in the main config")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_precedence() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-precedence3");
+        let mut f = File::create(setup.path())?;
+        f.write_all(b"precedence lats")?;
+
+        let diagnostics = match load(&setup.path()) {
+            Err(Error::InvalidConfig(diagnostics)) => diagnostics,
+            result => panic!("Expected InvalidConfig, found {:?}", result),
+        };
+        assert_eq!(diagnostics[0].error, ParseError::UnknownSymbol(setup.path(), 1));
+        assert_eq!(diagnostics[0].suggestion, Some(String::from("last")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable_warnings() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-warnings3");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"remap capslock -> escape
+taphold space space ctrl
+super + k
+    notify-send k
+super + ~k
+    notify-send passthrough
+capslock
+    notify-send caps
+super + space
+    notify-send space",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        let found: Vec<String> =
+            bindings.warnings.iter().map(|warning| warning.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "warning: `super + ~KEY_K` and `super + KEY_K` at /tmp/swhkd-test-warnings3:3 are \
+                 both bound, so whether the key is passed through depends on their order
+ --> /tmp/swhkd-test-warnings3:5",
+                "warning: `KEY_CAPSLOCK` can never trigger, `KEY_CAPSLOCK` is remapped to `KEY_ESC`
+ --> /tmp/swhkd-test-warnings3:7",
+                "warning: `super + KEY_SPACE` can never trigger, its key is a tap-hold key
+ --> /tmp/swhkd-test-warnings3:9",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_warnings() -> std::io::Result<()> {
+        let setup = TestPath::new("/tmp/swhkd-test-warnings4");
+        let mut f = File::create(setup.path())?;
+        f.write_all(
+            b"super + a; b
+    notify-send b
+super + a; c
+    notify-send c
+super + {d, e}
+    notify-send {d, e}",
+        )?;
+
+        let bindings = load_with_layout(&setup.path(), None, None).unwrap();
+        assert_eq!(bindings.warnings, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_common_modifiers() -> std::io::Result<()> {
+        let contents = "
+shift + k
+    notify-send 'Hello world!'
+
+control + 5
+    notify-send 'Hello world!'
+
+alt + 2
+    notify-send 'Hello world!'
+
+super + z
+    notify-send 'Hello world!'
+            ";
+
+        let expected_hotkeys = vec![
+            Hotkey::new(
+                evdev::Key::KEY_K,
+                vec![Modifier::Shift],
+                "notify-send 'Hello world!'".to_string(),
+            ),
+            Hotkey::new(
+                evdev::Key::KEY_5,
+                vec![Modifier::Control],
+                "notify-send 'Hello world!'".to_string(),
+            ),
+            Hotkey::new(
+                evdev::Key::KEY_2,
+                vec![Modifier::Alt],
+                "notify-send 'Hello world!'".to_string(),
+            ),
+            Hotkey::new(
+                evdev::Key::KEY_Z,
+                vec![Modifier::Super],
+                "notify-send 'Hello world!'".to_string(),
+            ),
+        ];
+
+        eval_config_test(contents, expected_hotkeys)
+    }
+
+    #[test]
+    fn test_command_with_many_spaces() -> std::io::Result<()> {
+        let contents = "
+p
+    xbacklight -inc 10 -fps 30 -time 200
+        ";
+
+        let expected_keybinds = vec![Hotkey::new(
+            evdev::Key::KEY_P,
+            vec![],
+            String::from("xbacklight -inc 10 -fps 30 -time 200"),
+        )];
+
+        eval_config_test(contents, expected_keybinds)
+    }
+
+    #[test]
+    fn test_invalid_keybinding() -> std::io::Result<()> {
+        let contents = "
+p
+    xbacklight -inc 10 -fps 30 -time 200
+
+pesto
+    xterm
+                    ";
+
+        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 5))
+    }
+
+    #[test]
+    // keysyms not followed by command should be ignored
+    fn test_no_command() -> std::io::Result<()> {
+        let contents = "
+k
+    xbacklight -inc 10 -fps 30 -time 200
+
+w
+
+                    ";
+
+        eval_config_test(
+            contents,
+            vec![Hotkey::new(
+                evdev::Key::KEY_K,
+                vec![],
+                "xbacklight -inc 10 -fps 30 -time 200".to_string(),
+            )],
+        )
+    }
+
+    #[test]
+    fn test_real_config_snippet() -> std::io::Result<()> {
+        let contents = "
+# reloads sxhkd configuration:
+super + Escape
+    pkill -USR1 -x sxhkd ; sxhkd &
+
+# Launch Terminal
+super + Return
+    alacritty -t \"Terminal\" -e \"$HOME/.config/sxhkd/new_tmux_terminal.sh\"
+
+# terminal emulator (no tmux)
+super + shift + Return
+    alacritty -t \"Terminal\"
+
+# terminal emulator (new tmux session)
+alt + Return
+    alacritty -t \"Terminal\" -e \"tmux\"
+
+ctrl + 0
+    play-song.sh
+
+super + minus
+    play-song.sh album
+                    ";
+
+        let expected_result: Vec<Hotkey> = vec![
+            Hotkey::new(
+                evdev::Key::KEY_ESC,
+                vec![Modifier::Super],
+                String::from("pkill -USR1 -x sxhkd ; sxhkd &"),
+            ),
+            Hotkey::new(
+                evdev::Key::KEY_ENTER,
+                vec![Modifier::Super],
+                String::from(
+                    "alacritty -t \"Terminal\" -e \"$HOME/.config/sxhkd/new_tmux_terminal.sh\"",
+                ),
+            ),
+            Hotkey::new(
+                evdev::Key::KEY_ENTER,
+                vec![Modifier::Super, Modifier::Shift],
+                String::from("alacritty -t \"Terminal\""),
+            ),
+            Hotkey::new(
+                evdev::Key::KEY_ENTER,
+                vec![Modifier::Alt],
+                String::from("alacritty -t \"Terminal\" -e \"tmux\""),
+            ),
+            Hotkey::new(evdev::Key::KEY_0, vec![Modifier::Control], String::from("play-song.sh")),
+            Hotkey::new(
+                evdev::Key::KEY_MINUS,
+                vec![Modifier::Super],
+                String::from("play-song.sh album"),
+            ),
+        ];
+
+        eval_config_test(contents, expected_result)
+    }
+
+    #[test]
+    fn test_multiline_command() -> std::io::Result<()> {
+        let contents = "
+k
+    mpc ls | dmenu | \\
+    sed -i 's/foo/bar/g'
+                    ";
+
+        let expected_keybind = Hotkey::new(
+            evdev::Key::KEY_K,
+            vec![],
+            String::from("mpc ls | dmenu | sed -i 's/foo/bar/g'"),
+        );
+
+        eval_config_test(contents, vec![expected_keybind])
+    }
+
+    #[test]
+    fn test_commented_out_keybind() -> std::io::Result<()> {
+        let contents = "
+#w
+    gimp
+                    ";
+
+        eval_config_test(contents, vec![])
+    }
+
+    // TODO: Write these tests as needed.
+
+    #[test]
+    fn test_all_alphanumeric() -> std::io::Result<()> {
+        let symbols: [&str; 36] = [
+            "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q",
+            "r", "s", "t", "u", "v", "w", "x", "y", "z", "0", "1", "2", "3", "4", "5", "6", "7",
+            "8", "9",
+        ];
+        let keysyms: [evdev::Key; 36] = [
+            evdev::Key::KEY_A,
+            evdev::Key::KEY_B,
+            evdev::Key::KEY_C,
+            evdev::Key::KEY_D,
+            evdev::Key::KEY_E,
+            evdev::Key::KEY_F,
+            evdev::Key::KEY_G,
+            evdev::Key::KEY_H,
+            evdev::Key::KEY_I,
+            evdev::Key::KEY_J,
+            evdev::Key::KEY_K,
+            evdev::Key::KEY_L,
+            evdev::Key::KEY_M,
+            evdev::Key::KEY_N,
+            evdev::Key::KEY_O,
+            evdev::Key::KEY_P,
+            evdev::Key::KEY_Q,
+            evdev::Key::KEY_R,
+            evdev::Key::KEY_S,
+            evdev::Key::KEY_T,
+            evdev::Key::KEY_U,
+            evdev::Key::KEY_V,
+            evdev::Key::KEY_W,
+            evdev::Key::KEY_X,
+            evdev::Key::KEY_Y,
+            evdev::Key::KEY_Z,
+            evdev::Key::KEY_0,
+            evdev::Key::KEY_1,
+            evdev::Key::KEY_2,
+            evdev::Key::KEY_3,
+            evdev::Key::KEY_4,
+            evdev::Key::KEY_5,
+            evdev::Key::KEY_6,
+            evdev::Key::KEY_7,
+            evdev::Key::KEY_8,
+            evdev::Key::KEY_9,
+        ];
+
+        let mut contents = String::new();
+        for symbol in &symbols {
+            contents.push_str(&format!("{}\n    st\n", symbol));
+        }
+        let contents = &contents;
+
+        let expected_result: Vec<Hotkey> =
+            keysyms.iter().map(|keysym| Hotkey::new(*keysym, vec![], "st".to_string())).collect();
+
+        eval_config_test(contents, expected_result)
+    }
+
+    #[test]
+    fn test_homerow_special_keys_top() -> std::io::Result<()> {
+        let symbols: [&str; 7] =
+            ["Escape", "BackSpace", "Return", "Tab", "minus", "equal", "grave"];
+
+        let keysyms: [evdev::Key; 7] = [
+            evdev::Key::KEY_ESC,
+            evdev::Key::KEY_BACKSPACE,
+            evdev::Key::KEY_ENTER,
+            evdev::Key::KEY_TAB,
+            evdev::Key::KEY_MINUS,
+            evdev::Key::KEY_EQUAL,
+            evdev::Key::KEY_GRAVE,
+        ];
+
+        let mut contents = String::new();
+        for symbol in &symbols {
+            contents.push_str(&format!("{}\n    st\n", symbol));
+        }
+        let contents = &contents;
+
+        let expected_result: Vec<Hotkey> =
+            keysyms.iter().map(|keysym| Hotkey::new(*keysym, vec![], "st".to_string())).collect();
+
+        eval_config_test(contents, expected_result)
+    }
+
+    #[test]
+    fn test_case_insensitive() -> std::io::Result<()> {
+        let contents = "
+Super + SHIFT + alt + a
+    st
+ReTurn
+    ts
+            ";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_A,
+                    vec![Modifier::Super, Modifier::Shift, Modifier::Alt],
+                    "st".to_string(),
+                ),
+                Hotkey::new(evdev::Key::KEY_ENTER, vec![], "ts".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_duplicate_hotkeys() -> std::io::Result<()> {
+        let contents = "
+super + shift + a
+    st
+shift + suPer +   A
+    ts
+b    
+    st
+B
+    ts
+";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_A,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "st".to_string(),
+                ),
+                Hotkey::new(evdev::Key::KEY_B, vec![], "st".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_inline_comment() -> std::io::Result<()> {
+        let contents = "
+super + a #comment and comment super
+    st
+super + shift + b
+    ts #this comment should be handled by shell
+"
+        .to_string();
+        eval_config_test(
+            &contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "st".to_string()),
+                Hotkey::new(
+                    evdev::Key::KEY_B,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "ts #this comment should be handled by shell".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_blank_config() -> std::io::Result<()> {
+        let contents = "";
+
+        eval_config_test(contents, vec![])
+    }
+
+    #[test]
+    fn test_blank_config_with_whitespace() -> std::io::Result<()> {
+        let contents = "
+
+
+            ";
+
+        eval_config_test(contents, vec![])
+    }
+
+    #[test]
+    fn test_extract_curly_brace() -> std::io::Result<()> {
+        let keybind_with_curly_brace = "super + {a,b,c}";
+        assert_eq!(
+            extract_curly_brace(keybind_with_curly_brace),
+            vec!["super + a", "super + b", "super + c",]
+        );
+        let command_with_curly_brace = "bspc node -p {west,south,north,west}";
+        assert_eq!(
+            extract_curly_brace(command_with_curly_brace),
+            vec![
+                "bspc node -p west",
+                "bspc node -p south",
+                "bspc node -p north",
+                "bspc node -p west",
+            ]
+        );
+        let wrong_format = "super + }a, b, c{";
+        assert_eq!(extract_curly_brace(wrong_format), vec![wrong_format]);
+        let single_sym = "super + {a}";
+        assert_eq!(extract_curly_brace(single_sym), vec!["super + a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_curly_brace() -> std::io::Result<()> {
+        let contents = "
+super + {a,b,c}
+    {firefox, brave, chrome}";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
+                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
+                Hotkey::new(evdev::Key::KEY_C, vec![Modifier::Super], "chrome".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_curly_brace_less_commands() -> std::io::Result<()> {
+        let contents = "
+super + {a,b,c}
+    {firefox, brave}";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
+                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_curly_brace_less_keysyms() -> std::io::Result<()> {
+        let contents = "
+super + {a, b}
+    {firefox, brave, chrome}";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
+                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_range_syntax() -> std::io::Result<()> {
+        let contents = "
+super + {1-9,0}
+    bspc desktop -f '{1-9,0}'";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '1'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_2,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '2'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_3,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '3'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_4,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '4'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_5,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '5'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_6,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '6'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_7,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '7'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_8,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '8'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_9,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '9'".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_0,
+                    vec![Modifier::Super],
+                    "bspc desktop -f '0'".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_range_syntax_ascii_character() -> std::io::Result<()> {
+        let contents = "
+super + {a-c}
+    {firefox, brave, chrome}";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
+                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
+                Hotkey::new(evdev::Key::KEY_C, vec![Modifier::Super], "chrome".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_range_syntax_not_ascii() -> std::io::Result<()> {
+        let contents = "
+super + {a-是}
+    {firefox, brave}
+    ";
+        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 2))
+    }
+
+    #[test]
+    fn test_range_syntax_invalid_range() -> std::io::Result<()> {
+        let contents = "
+super + {bc-ad}
+    {firefox, brave}
+    ";
+        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 2))
+    }
+
+    #[test]
+    fn test_ranger_syntax_not_full_range() -> std::io::Result<()> {
+        let contents = "
+super + {a-}
+    {firefox, brave}";
+        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 2))
+    }
+
+    #[test]
+    fn test_none() -> std::io::Result<()> {
+        let contents = "
+super + {_, shift} + b
+    {firefox, brave}";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "firefox".to_string()),
+                Hotkey::new(
+                    evdev::Key::KEY_B,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "brave".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_multiple_ranges() -> std::io::Result<()> {
+        let contents = "
+super + {shift,alt} + {c,d}
+    {librewolf, firefox} {--sync, --help}
+            ";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_C,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "librewolf --sync".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_D,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "librewolf --help".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_C,
+                    vec![Modifier::Super, Modifier::Alt],
+                    "firefox --sync".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_D,
+                    vec![Modifier::Super, Modifier::Alt],
+                    "firefox --help".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_multiple_ranges_numbers() -> std::io::Result<()> {
+        let contents = "
+{control,super} + {1-3}
+    {notify-send, echo} {hello,how,are}
+            ";
 
-        let mut contents = String::new();
-        for symbol in &symbols {
-            contents.push_str(&format!("{}\n    st\n", symbol));
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Control],
+                    "notify-send hello".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_2,
+                    vec![Modifier::Control],
+                    "notify-send how".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_3,
+                    vec![Modifier::Control],
+                    "notify-send are".to_string(),
+                ),
+                Hotkey::new(evdev::Key::KEY_1, vec![Modifier::Super], "echo hello".to_string()),
+                Hotkey::new(evdev::Key::KEY_2, vec![Modifier::Super], "echo how".to_string()),
+                Hotkey::new(evdev::Key::KEY_3, vec![Modifier::Super], "echo are".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_bspwm_multiple_curly_brace() -> std::io::Result<()> {
+        let contents = "
+super + {_,shift + }{h,j,k,l}
+	bspc node -{f,s} {west,south,north,east}";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_H,
+                    vec![Modifier::Super],
+                    "bspc node -f west".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_J,
+                    vec![Modifier::Super],
+                    "bspc node -f south".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_K,
+                    vec![Modifier::Super],
+                    "bspc node -f north".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_L,
+                    vec![Modifier::Super],
+                    "bspc node -f east".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_H,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "bspc node -s west".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_J,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "bspc node -s south".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_K,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "bspc node -s north".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_L,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "bspc node -s east".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_longer_multiple_curly_brace() -> std::io::Result<()> {
+        let contents = "
+super + {_, ctrl +} {_, shift +} {1-2}
+    riverctl {set, toggle}-{focused, view}-tags {1-2}";
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super],
+                    "riverctl set-focused-tags 1".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_2,
+                    vec![Modifier::Super],
+                    "riverctl set-focused-tags 2".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super, Modifier::Control],
+                    "riverctl toggle-focused-tags 1".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_2,
+                    vec![Modifier::Super, Modifier::Control],
+                    "riverctl toggle-focused-tags 2".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "riverctl set-view-tags 1".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_2,
+                    vec![Modifier::Super, Modifier::Shift],
+                    "riverctl set-view-tags 2".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_1,
+                    vec![Modifier::Super, Modifier::Control, Modifier::Shift],
+                    "riverctl toggle-view-tags 1".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_2,
+                    vec![Modifier::Super, Modifier::Control, Modifier::Shift],
+                    "riverctl toggle-view-tags 2".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_period_binding() -> std::io::Result<()> {
+        let contents = "
+super + {comma, period}
+	riverctl focus-output {previous, next}";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_COMMA,
+                    vec![Modifier::Super],
+                    "riverctl focus-output previous".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_DOT,
+                    vec![Modifier::Super],
+                    "riverctl focus-output next".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_period_escape_binding() -> std::io::Result<()> {
+        let contents = "
+super + {\\,, .}
+	riverctl focus-output {previous, next}";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_COMMA,
+                    vec![Modifier::Super],
+                    "riverctl focus-output previous".to_string(),
+                ),
+                Hotkey::new(
+                    evdev::Key::KEY_DOT,
+                    vec![Modifier::Super],
+                    "riverctl focus-output next".to_string(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_prefix() -> std::io::Result<()> {
+        let contents = "
+super + @1
+    1
+super + ~2
+    2
+super + ~@3
+    3
+super + @~4
+    4";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_1, vec![Modifier::Super], "1".to_string()).on_release(),
+                Hotkey::new(evdev::Key::KEY_2, vec![Modifier::Super], "2".to_string()).send(),
+                Hotkey::new(evdev::Key::KEY_3, vec![Modifier::Super], "3".to_string())
+                    .on_release()
+                    .send(),
+                Hotkey::new(evdev::Key::KEY_4, vec![Modifier::Super], "4".to_string())
+                    .on_release()
+                    .send(),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_x11_keysyms() -> std::io::Result<()> {
+        let contents = "
+XF86AudioMicMute
+    1
+super + KP_Enter
+    2
+Menu
+    3
+ctrl + Super_L
+    4
+XF86Calculator
+    5
+super + exclam
+    6
+Prior
+    7
+XF86AudioRaiseVolume
+    8";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_MICMUTE, vec![], "1".to_string()),
+                Hotkey::new(evdev::Key::KEY_KPENTER, vec![Modifier::Super], "2".to_string()),
+                Hotkey::new(evdev::Key::KEY_COMPOSE, vec![], "3".to_string()),
+                Hotkey::new(evdev::Key::KEY_LEFTMETA, vec![Modifier::Control], "4".to_string()),
+                Hotkey::new(evdev::Key::KEY_CALC, vec![], "5".to_string()),
+                Hotkey::new(evdev::Key::KEY_1, vec![Modifier::Super], "6".to_string()),
+                Hotkey::new(evdev::Key::KEY_PAGEUP, vec![], "7".to_string()),
+                Hotkey::new(evdev::Key::KEY_VOLUMEUP, vec![], "8".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_legacy_keysyms() -> std::io::Result<()> {
+        // `plus` keeps the keypad key it was bound to before the generated table. The others
+        // were bound to the wrong key before, and now follow X11.
+        let contents = "
+super + plus
+    1
+Next
+    2
+question
+    3
+XF86AudioStop
+    4";
+
+        eval_config_test(
+            contents,
+            vec![
+                Hotkey::new(evdev::Key::KEY_KPPLUS, vec![Modifier::Super], "1".to_string()),
+                Hotkey::new(evdev::Key::KEY_PAGEDOWN, vec![], "2".to_string()),
+                Hotkey::new(evdev::Key::KEY_SLASH, vec![], "3".to_string()),
+                Hotkey::new(evdev::Key::KEY_STOPCD, vec![], "4".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_legacy_keysyms_follow_the_layout() {
+        assert_eq!(key_to_evdev_key(None)["plus"], evdev::Key::KEY_KPPLUS);
+        assert_eq!(key_to_evdev_key(None)["kp_add"], evdev::Key::KEY_KPPLUS);
+        // On a German layout `plus` is a key of its own
+        let layout = HashMap::from([(String::from("plus"), evdev::Key::KEY_RIGHTBRACE)]);
+        assert_eq!(key_to_evdev_key(Some(layout))["plus"], evdev::Key::KEY_RIGHTBRACE);
+    }
+
+    #[test]
+    fn test_keysym_table_is_lowercase() {
+        for (name, _) in crate::keysyms::KEYSYMS {
+            assert_eq!(*name, name.to_lowercase());
         }
-        let contents = &contents;
+    }
+
+    #[test]
+    fn test_keychord_chain() -> std::io::Result<()> {
+        let contents = "
+super + a ; {b, c}
+    {firefox, alacritty}";
+
+        let hotkeys = parse_contents(PathBuf::new(), contents.to_string()).unwrap();
+        assert_eq!(
+            hotkeys,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_A,
+                    vec![Modifier::Super],
+                    "@enter ;super+a oneoff".to_string()
+                ),
+                Hotkey::new(evdev::Key::KEY_B, vec![], "firefox".to_string())
+                    .in_mode(Some(";super+a".to_string())),
+                Hotkey::new(evdev::Key::KEY_C, vec![], "alacritty".to_string())
+                    .in_mode(Some(";super+a".to_string())),
+            ]
+        );
+        assert_eq!(
+            hotkeys[0].mode_action(),
+            Some(ModeAction::Enter { name: ";super+a".to_string(), oneoff: true })
+        );
+        Ok(())
+    }
 
-        let expected_result: Vec<Hotkey> =
-            keysyms.iter().map(|keysym| Hotkey::new(*keysym, vec![], "st".to_string())).collect();
+    #[test]
+    fn test_locked_keychord_chain() -> std::io::Result<()> {
+        let contents = "
+super + r ; x : {h, l}
+    bspc node -z {left, right} 20 0";
 
-        eval_config_test(contents, expected_result)
+        let hotkeys = parse_contents(PathBuf::new(), contents.to_string()).unwrap();
+        assert_eq!(
+            hotkeys,
+            vec![
+                Hotkey::new(
+                    evdev::Key::KEY_R,
+                    vec![Modifier::Super],
+                    "@enter ;super+r oneoff".to_string()
+                ),
+                Hotkey::new(evdev::Key::KEY_X, vec![], "@enter ;super+r;x".to_string())
+                    .in_mode(Some(";super+r".to_string())),
+                Hotkey::new(evdev::Key::KEY_H, vec![], "bspc node -z left 20 0".to_string())
+                    .in_mode(Some(";super+r;x".to_string())),
+                Hotkey::new(evdev::Key::KEY_L, vec![], "bspc node -z right 20 0".to_string())
+                    .in_mode(Some(";super+r;x".to_string())),
+            ]
+        );
+        assert_eq!(
+            hotkeys[1].mode_action(),
+            Some(ModeAction::Enter { name: ";super+r;x".to_string(), oneoff: false })
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_homerow_special_keys_top() -> std::io::Result<()> {
-        let symbols: [&str; 7] =
-            ["Escape", "BackSpace", "Return", "Tab", "minus", "equal", "grave"];
+    fn test_semicolon_is_not_a_chain() -> std::io::Result<()> {
+        let contents = "
+super + ;
+    notify-send semicolon";
 
-        let keysyms: [evdev::Key; 7] = [
-            evdev::Key::KEY_ESC,
-            evdev::Key::KEY_BACKSPACE,
-            evdev::Key::KEY_ENTER,
-            evdev::Key::KEY_TAB,
-            evdev::Key::KEY_MINUS,
-            evdev::Key::KEY_EQUAL,
-            evdev::Key::KEY_GRAVE,
-        ];
+        eval_config_test(
+            contents,
+            vec![Hotkey::new(
+                evdev::Key::KEY_SEMICOLON,
+                vec![Modifier::Super],
+                "notify-send semicolon".to_string(),
+            )],
+        )
+    }
 
-        let mut contents = String::new();
-        for symbol in &symbols {
-            contents.push_str(&format!("{}\n    st\n", symbol));
-        }
-        let contents = &contents;
+    #[test]
+    fn test_mode() -> std::io::Result<()> {
+        let contents = "
+super + m
+    @enter music
 
-        let expected_result: Vec<Hotkey> =
-            keysyms.iter().map(|keysym| Hotkey::new(*keysym, vec![], "st".to_string())).collect();
+mode music
+n
+    mpc next
+escape
+    @escape
+endmode
 
-        eval_config_test(contents, expected_result)
+n
+    notify-send n";
+
+        let hotkeys = parse_contents(PathBuf::new(), contents.to_string()).unwrap();
+        assert_eq!(
+            hotkeys,
+            vec![
+                Hotkey::new(evdev::Key::KEY_M, vec![Modifier::Super], "@enter music".to_string()),
+                Hotkey::new(evdev::Key::KEY_N, vec![], "mpc next".to_string())
+                    .in_mode(Some("music".to_string())),
+                Hotkey::new(evdev::Key::KEY_ESC, vec![], "@escape".to_string())
+                    .in_mode(Some("music".to_string())),
+                Hotkey::new(evdev::Key::KEY_N, vec![], "notify-send n".to_string()),
+            ]
+        );
+        assert_eq!(
+            hotkeys[0].mode_action(),
+            Some(ModeAction::Enter { name: "music".to_string(), oneoff: false })
+        );
+        assert_eq!(hotkeys[2].mode_action(), Some(ModeAction::Escape));
+        assert_eq!(hotkeys[3].mode_action(), None);
+        Ok(())
     }
 
     #[test]
-    fn test_case_insensitive() -> std::io::Result<()> {
+    fn test_raw_keycodes() -> std::io::Result<()> {
         let contents = "
-Super + SHIFT + alt + a
-    st
-ReTurn
-    ts
-            ";
+code:183
+    1
+super + KEY_PROG1
+    2
+BTN_SIDE
+    3
+ctrl + Code:30
+    4";
+
         eval_config_test(
             contents,
             vec![
-                Hotkey::new(
-                    evdev::Key::KEY_A,
-                    vec![Modifier::Super, Modifier::Shift, Modifier::Alt],
-                    "st".to_string(),
-                ),
-                Hotkey::new(evdev::Key::KEY_ENTER, vec![], "ts".to_string()),
+                Hotkey::new(evdev::Key::KEY_F13, vec![], "1".to_string()),
+                Hotkey::new(evdev::Key::KEY_PROG1, vec![Modifier::Super], "2".to_string()),
+                Hotkey::new(evdev::Key::BTN_SIDE, vec![], "3".to_string()),
+                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Control], "4".to_string()),
             ],
         )
     }
 
     #[test]
-    fn test_duplicate_hotkeys() -> std::io::Result<()> {
+    fn test_raw_keycode_in_chain() -> std::io::Result<()> {
         let contents = "
-super + shift + a
-    st
-shift + suPer +   A
-    ts
-b    
-    st
-B
-    ts
-";
-        eval_config_test(
-            contents,
+super + code:30 ; code:48
+    notify-send chain";
+
+        let hotkeys = parse_contents(PathBuf::new(), contents.to_string()).unwrap();
+        assert_eq!(
+            hotkeys,
             vec![
                 Hotkey::new(
                     evdev::Key::KEY_A,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "st".to_string(),
+                    vec![Modifier::Super],
+                    "@enter ;super+code:30 oneoff".to_string()
                 ),
-                Hotkey::new(evdev::Key::KEY_B, vec![], "st".to_string()),
-            ],
-        )
+                Hotkey::new(evdev::Key::KEY_B, vec![], "notify-send chain".to_string())
+                    .in_mode(Some(";super+code:30".to_string())),
+            ]
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_inline_comment() -> std::io::Result<()> {
-        let contents = "
-super + a #comment and comment super
-    st
-super + shift + b
-    ts #this comment should be handled by shell
-"
-        .to_string();
-        eval_config_test(
-            &contents,
-            vec![
-                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "st".to_string()),
-                Hotkey::new(
-                    evdev::Key::KEY_B,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "ts #this comment should be handled by shell".to_string(),
+    fn test_invalid_raw_keycodes() -> std::io::Result<()> {
+        for keysym in ["code:768", "code:", "code:-1", "KEY_NOPE", "BTN_SIDEWAYS"] {
+            let contents = format!("\nsuper + {}\n    notify-send invalid", keysym);
+            eval_invalid_config_test(&contents, ParseError::UnknownSymbol(PathBuf::new(), 2))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_actions() -> std::io::Result<()> {
+        let contents = r#"
+super + e
+    @type "Hi, \"you\"!\n"
+super + v
+    @keys ctrl + shift + v Return
+super + t
+    @typewriter"#;
+
+        let hotkeys = parse_contents(PathBuf::new(), contents.to_string()).unwrap();
+        let shift = || vec![evdev::Key::KEY_LEFTSHIFT];
+        assert_eq!(
+            hotkeys[0].keystrokes,
+            Some(vec![
+                KeyStroke::new(evdev::Key::KEY_H, shift()),
+                KeyStroke::new(evdev::Key::KEY_I, vec![]),
+                KeyStroke::new(evdev::Key::KEY_COMMA, vec![]),
+                KeyStroke::new(evdev::Key::KEY_SPACE, vec![]),
+                KeyStroke::new(evdev::Key::KEY_APOSTROPHE, shift()),
+                KeyStroke::new(evdev::Key::KEY_Y, vec![]),
+                KeyStroke::new(evdev::Key::KEY_O, vec![]),
+                KeyStroke::new(evdev::Key::KEY_U, vec![]),
+                KeyStroke::new(evdev::Key::KEY_APOSTROPHE, shift()),
+                KeyStroke::new(evdev::Key::KEY_1, shift()),
+                KeyStroke::new(evdev::Key::KEY_ENTER, vec![]),
+            ])
+        );
+        assert_eq!(
+            hotkeys[1].keystrokes,
+            Some(vec![
+                KeyStroke::new(
+                    evdev::Key::KEY_V,
+                    vec![evdev::Key::KEY_LEFTCTRL, evdev::Key::KEY_LEFTSHIFT]
                 ),
-            ],
+                KeyStroke::new(evdev::Key::KEY_ENTER, vec![]),
+            ])
+        );
+        assert_eq!(hotkeys[2].keystrokes, None);
+        assert_eq!(hotkeys[2].command, "@typewriter");
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_key_actions() -> std::io::Result<()> {
+        eval_invalid_config_test(
+            "super + e\n    @type \"\u{1f600}\"",
+            ParseError::UnknownSymbol(PathBuf::new(), 2),
+        )?;
+        eval_invalid_config_test(
+            "super + e\n    @keys ctrl + nope",
+            ParseError::UnknownSymbol(PathBuf::new(), 2),
+        )?;
+        eval_invalid_config_test(
+            "super + e\n    @keys",
+            ParseError::UnknownSymbol(PathBuf::new(), 2),
         )
     }
+}
+
+mod test_config_display {
+    use crate::config::{Diagnostic, Error, ParseError};
+    use std::io;
+    use std::path::PathBuf;
+
+    fn diagnostic(error: ParseError, source_line: &str, token: &str) -> Diagnostic {
+        let start = source_line.find(token).unwrap();
+        Diagnostic {
+            error,
+            token: token.to_string(),
+            source_line: source_line.to_string(),
+            columns: start..start + token.len(),
+            suggestion: None,
+        }
+    }
 
     #[test]
-    fn test_blank_config() -> std::io::Result<()> {
-        let contents = "";
+    fn test_display_io_error() {
+        let error = Error::Io(io::Error::from(io::ErrorKind::UnexpectedEof));
 
-        eval_config_test(contents, vec![])
+        if !format!("{}", error).contains("unexpected end of file") {
+            panic!("Error message was '{}", error);
+        }
     }
 
     #[test]
-    fn test_blank_config_with_whitespace() -> std::io::Result<()> {
-        let contents = "
+    fn test_display_unknown_symbol_error() {
+        let mut diagnostic = diagnostic(
+            ParseError::UnknownSymbol(PathBuf::from("/etc/swhkd/swhkdrc"), 10),
+            "supr + a",
+            "supr",
+        );
+        diagnostic.suggestion = Some(String::from("super"));
+        let error = Error::InvalidConfig(vec![diagnostic]);
 
+        assert_eq!(
+            format!("{}", error),
+            "error: unknown symbol `supr`
+  --> /etc/swhkd/swhkdrc:10:1
+   |
+10 | supr + a
+   | ^^^^
+   = help: did you mean `super`?
+
+Found 1 error in the config."
+        );
+    }
 
-            ";
+    #[test]
+    fn test_display_invalid_modifier_error() {
+        let error = Error::InvalidConfig(vec![diagnostic(
+            ParseError::InvalidModifier(PathBuf::new(), 25),
+            "super + a + b",
+            "a",
+        )]);
 
-        eval_config_test(contents, vec![])
+        assert_eq!(
+            format!("{}", error),
+            "error: invalid modifier `a`
+  --> :25:9
+   |
+25 | super + a + b
+   |         ^
+
+Found 1 error in the config."
+        );
+    }
+
+    #[test]
+    fn test_invalid_keysm_error() {
+        let error = Error::InvalidConfig(vec![diagnostic(
+            ParseError::InvalidKeysym(PathBuf::new(), 7),
+            "super + shift",
+            "shift",
+        )]);
+
+        assert_eq!(
+            format!("{}", error),
+            "error: invalid keysym `shift`
+ --> :7:9
+  |
+7 | super + shift
+  |         ^^^^^
+
+Found 1 error in the config."
+        );
+    }
+
+    #[test]
+    fn test_display_several_errors() {
+        let error = Error::InvalidConfig(vec![
+            diagnostic(ParseError::UnknownSymbol(PathBuf::new(), 1), "supr + a", "supr"),
+            diagnostic(ParseError::UnknownSymbol(PathBuf::new(), 3), "super + bb", "bb"),
+        ]);
+
+        assert!(format!("{}", error).ends_with("^^\n\nFound 2 errors in the config."));
+    }
+}
+
+mod test_control {
+    use crate::config::{KeyBinding, Modifier, Prefix};
+    use crate::control::Request;
+
+    #[test]
+    fn test_parse_requests() {
+        assert_eq!("pause\n".parse::<Request>(), Ok(Request::Pause));
+        assert_eq!("resume".parse::<Request>(), Ok(Request::Resume));
+        assert_eq!("reload".parse::<Request>(), Ok(Request::Reload));
+        assert_eq!("status".parse::<Request>(), Ok(Request::Status));
+        assert_eq!("list-bindings".parse::<Request>(), Ok(Request::ListBindings));
+        assert_eq!("list-devices".parse::<Request>(), Ok(Request::ListDevices));
+        assert_eq!("mode resize\n".parse::<Request>(), Ok(Request::Mode("resize".to_string())));
+    }
+
+    #[test]
+    fn test_parse_invalid_requests() {
+        assert!("".parse::<Request>().is_err());
+        assert!("mode".parse::<Request>().is_err());
+        assert!("pause now".parse::<Request>().is_err());
+        assert!("shutdown".parse::<Request>().is_err());
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        for request in [Request::ListBindings, Request::Mode("music".to_string())] {
+            assert_eq!(request.to_string().parse::<Request>(), Ok(request));
+        }
+    }
+
+    #[test]
+    fn test_display_keybinding() {
+        let keybinding =
+            KeyBinding::new(evdev::Key::KEY_A, vec![Modifier::Super, Modifier::Shift]).send();
+        assert_eq!(keybinding.to_string(), "super + shift + ~KEY_A");
+        let keybinding = KeyBinding::new(evdev::Key::new(0x2fe), vec![]);
+        assert_eq!(keybinding.to_string(), "code:766");
+    }
+}
+
+mod test_layout {
+    use crate::config::{parse_contents, Config};
+    use crate::layout::{keysym_to_char, Layout};
+    use std::{fs, path::PathBuf};
+
+    // A minimal XKB tree with a German-like layout, removed when dropped
+    struct TestXkbRoot {
+        path: PathBuf,
+    }
+
+    impl TestXkbRoot {
+        fn new(path: &str) -> Self {
+            let path = PathBuf::from(path);
+            let files = [
+                (
+                    "keycodes/evdev",
+                    r#"default xkb_keycodes "evdev" {
+    <AE01> = 10; <AE02> = 11;
+    <AD01> = 24; <AD06> = 29;
+    <AB01> = 52; <AB08> = 59;
+    <I148> = 148;
+    alias <AC12> = <AB08>;
+};"#,
+                ),
+                (
+                    "symbols/pc",
+                    r#"default partial xkb_symbols "pc" {
+    key <AB08> { [ comma, less ] };
+};"#,
+                ),
+                (
+                    "symbols/inet",
+                    r#"partial xkb_symbols "evdev" {
+    key <I148> { [ XF86Calculator ] };
+};"#,
+                ),
+                (
+                    "symbols/latin",
+                    r#"// Shared by several layouts
+default partial xkb_symbols "basic" {
+    key <AE01> { [ 1, exclam ] };
+    key <AE02> { [ 2, quotedbl ] };
+    key <AD01> { [ q, Q, at ] };
+    key <AD06> { [ z, Z ] };
+    key <AB01> { [ y, Y ] };
+};"#,
+                ),
+                (
+                    "symbols/test",
+                    r#"default partial xkb_symbols "basic" {
+    include "latin"
+    name[Group1] = "Test";
+    key <AD06> { [ z, Z ] };
+    key <AB01> { [ y, Y ] };
+};
+
+/* AZERTY-like variant */
+partial xkb_symbols "azerty" {
+    include "test(basic)"
+    key <AD01> {
+        type[Group1] = "FOUR_LEVEL",
+        symbols[Group1] = [ a, A, at ]
+    };
+};"#,
+                ),
+            ];
+            for (file, contents) in files {
+                let file = path.join(file);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(file, contents).unwrap();
+            }
+            TestXkbRoot { path }
+        }
+    }
+
+    impl Drop for TestXkbRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    #[test]
+    fn test_layout_keysyms() {
+        let root = TestXkbRoot::new("/tmp/swhkd-test-xkb1");
+        let keysyms = Layout::new("test", None).keysyms(&root.path).unwrap();
+        assert_eq!(keysyms.get("z").map(|(key, _)| key), Some(&evdev::Key::KEY_Y));
+        assert_eq!(keysyms.get("y").map(|(key, _)| key), Some(&evdev::Key::KEY_Z));
+        assert_eq!(keysyms.get("exclam").map(|(key, _)| key), Some(&evdev::Key::KEY_1));
+        assert_eq!(keysyms.get("at"), Some(&(evdev::Key::KEY_Q, 2)));
+        assert_eq!(keysyms.get("exclam"), Some(&(evdev::Key::KEY_1, 1)));
+        assert_eq!(keysyms.get("less").map(|(key, _)| key), Some(&evdev::Key::KEY_COMMA));
+        assert_eq!(keysyms.get("xf86calculator").map(|(key, _)| key), Some(&evdev::Key::KEY_CALC));
+    }
+
+    #[test]
+    fn test_layout_variant() {
+        let root = TestXkbRoot::new("/tmp/swhkd-test-xkb2");
+        let keysyms = Layout::new("test", Some("azerty")).keysyms(&root.path).unwrap();
+        assert_eq!(keysyms.get("a").map(|(key, _)| key), Some(&evdev::Key::KEY_Q));
+        assert_eq!(keysyms.get("q").map(|(key, _)| key), None);
+        assert_eq!(keysyms.get("at").map(|(key, _)| key), Some(&evdev::Key::KEY_Q));
+        assert_eq!(keysyms.get("z").map(|(key, _)| key), Some(&evdev::Key::KEY_Y));
+    }
+
+    #[test]
+    fn test_unknown_layout() {
+        let root = TestXkbRoot::new("/tmp/swhkd-test-xkb3");
+        assert!(Layout::new("nope", None).keysyms(&root.path).is_err());
+        assert!(Layout::new("test", Some("nope")).keysyms(&root.path).is_err());
+    }
+
+    #[test]
+    fn test_keysym_to_char() {
+        assert_eq!(keysym_to_char("a"), Some('a'));
+        assert_eq!(keysym_to_char("exclam"), Some('!'));
+        assert_eq!(keysym_to_char("udiaeresis"), Some('ü'));
+        assert_eq!(keysym_to_char("u20ac"), Some('€'));
+        assert_eq!(keysym_to_char("return"), None);
+    }
+
+    #[test]
+    fn test_layout_statement() {
+        let contents = "layout de nodeadkeys\nsuper + a\n    a";
+        assert_eq!(Config::get_layout(contents), Some(Layout::new("de", Some("nodeadkeys"))));
+        assert_eq!(Config::get_layout("layout fr"), Some(Layout::new("fr", None)));
+        assert_eq!(Config::get_layout("super + a\n    a"), None);
+        assert_eq!(parse_contents(PathBuf::new(), contents.to_string()).unwrap().len(), 1);
+    }
+}
+
+mod test_remap {
+    use crate::config::{parse_contents, parse_remaps, KeyBinding, Modifier, Remap};
+    use crate::remap::{remap_event, ActiveRemap, Remapped};
+    use evdev::{AttributeSet, Key};
+    use std::collections::{HashMap, HashSet};
+    use std::path::PathBuf;
+
+    fn remaps() -> Vec<Remap> {
+        parse_remaps(
+            PathBuf::new(),
+            "
+remap capslock -> escape
+remap ctrl + h -> backspace
+remap super + {j,k} -> ctrl + {n,p}
+super + a
+    notify-send a",
+        )
+        .unwrap()
+    }
+
+    // Runs one key event through the remaps and returns the events sent to the output device
+    fn remap(
+        active_remaps: &mut HashMap<Key, ActiveRemap>,
+        modifier_keys: &[Key],
+        key: Key,
+        value: i32,
+    ) -> Option<Vec<(Key, i32)>> {
+        let mut modifier_keys_held = AttributeSet::new();
+        let mut modifiers = HashSet::new();
+        for key in modifier_keys {
+            modifier_keys_held.insert(*key);
+            modifiers.insert(match *key {
+                Key::KEY_LEFTCTRL | Key::KEY_RIGHTCTRL => Modifier::Control,
+                Key::KEY_LEFTSHIFT | Key::KEY_RIGHTSHIFT => Modifier::Shift,
+                Key::KEY_LEFTALT | Key::KEY_RIGHTALT => Modifier::Alt,
+                _ => Modifier::Super,
+            });
+        }
+        match remap_event(&remaps(), active_remaps, &modifiers, &modifier_keys_held, key, value)? {
+            Remapped::Key(key) => Some(vec![(key, value)]),
+            Remapped::Events(events) => {
+                Some(events.iter().map(|event| (Key::new(event.code()), event.value())).collect())
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_remaps() {
+        assert_eq!(
+            remaps(),
+            vec![
+                Remap::new(
+                    KeyBinding::new(Key::KEY_CAPSLOCK, vec![]),
+                    KeyBinding::new(Key::KEY_ESC, vec![])
+                ),
+                Remap::new(
+                    KeyBinding::new(Key::KEY_H, vec![Modifier::Control]),
+                    KeyBinding::new(Key::KEY_BACKSPACE, vec![])
+                ),
+                Remap::new(
+                    KeyBinding::new(Key::KEY_J, vec![Modifier::Super]),
+                    KeyBinding::new(Key::KEY_N, vec![Modifier::Control])
+                ),
+                Remap::new(
+                    KeyBinding::new(Key::KEY_K, vec![Modifier::Super]),
+                    KeyBinding::new(Key::KEY_P, vec![Modifier::Control])
+                ),
+            ]
+        );
+        assert_eq!(remaps()[1].to_string(), "ctrl + KEY_H -> KEY_BACKSPACE");
+    }
+
+    #[test]
+    fn test_remap_lines_are_not_hotkeys() {
+        let contents = "remap capslock -> escape\nsuper + a\n    notify-send a";
+        assert_eq!(parse_contents(PathBuf::new(), contents.to_string()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_remaps() {
+        for line in
+            ["remap capslock escape", "remap -> escape", "remap capslock ->", "remap nope -> a"]
+        {
+            assert!(parse_remaps(PathBuf::new(), line).is_err(), "{} should not parse", line);
+        }
     }
 
     #[test]
-    fn test_extract_curly_brace() -> std::io::Result<()> {
-        let keybind_with_curly_brace = "super + {a,b,c}";
+    fn test_key_remap() {
+        let mut active_remaps = HashMap::new();
         assert_eq!(
-            extract_curly_brace(keybind_with_curly_brace),
-            vec!["super + a", "super + b", "super + c",]
+            remap(&mut active_remaps, &[Key::KEY_LEFTSHIFT], Key::KEY_CAPSLOCK, 1),
+            Some(vec![(Key::KEY_ESC, 1)])
         );
-        let command_with_curly_brace = "bspc node -p {west,south,north,west}";
         assert_eq!(
-            extract_curly_brace(command_with_curly_brace),
-            vec![
-                "bspc node -p west",
-                "bspc node -p south",
-                "bspc node -p north",
-                "bspc node -p west",
-            ]
+            remap(&mut active_remaps, &[], Key::KEY_CAPSLOCK, 2),
+            Some(vec![(Key::KEY_ESC, 2)])
         );
-        let wrong_format = "super + }a, b, c{";
-        assert_eq!(extract_curly_brace(wrong_format), vec![wrong_format]);
-        let single_sym = "super + {a}";
-        assert_eq!(extract_curly_brace(single_sym), vec!["super + a"]);
-        Ok(())
+        assert_eq!(
+            remap(&mut active_remaps, &[], Key::KEY_CAPSLOCK, 0),
+            Some(vec![(Key::KEY_ESC, 0)])
+        );
+        assert_eq!(remap(&mut active_remaps, &[], Key::KEY_A, 1), None);
+        assert!(active_remaps.is_empty());
     }
 
     #[test]
-    fn test_curly_brace() -> std::io::Result<()> {
-        let contents = "
-super + {a,b,c}
-    {firefox, brave, chrome}";
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
-                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
-                Hotkey::new(evdev::Key::KEY_C, vec![Modifier::Super], "chrome".to_string()),
-            ],
-        )
+    fn test_chord_remap() {
+        let mut active_remaps = HashMap::new();
+        let held = [Key::KEY_LEFTCTRL, Key::KEY_LEFTSHIFT];
+        assert_eq!(
+            remap(&mut active_remaps, &held, Key::KEY_H, 1),
+            Some(vec![(Key::KEY_LEFTCTRL, 0), (Key::KEY_BACKSPACE, 1)])
+        );
+        assert_eq!(
+            remap(&mut active_remaps, &held, Key::KEY_H, 2),
+            Some(vec![(Key::KEY_BACKSPACE, 2)])
+        );
+        assert_eq!(
+            remap(&mut active_remaps, &held, Key::KEY_H, 0),
+            Some(vec![(Key::KEY_BACKSPACE, 0), (Key::KEY_LEFTCTRL, 1)])
+        );
+        assert_eq!(remap(&mut active_remaps, &[], Key::KEY_H, 1), None);
     }
 
     #[test]
-    fn test_curly_brace_less_commands() -> std::io::Result<()> {
-        let contents = "
-super + {a,b,c}
-    {firefox, brave}";
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
-                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
-            ],
-        )
+    fn test_chord_remap_to_chord() {
+        let mut active_remaps = HashMap::new();
+        assert_eq!(
+            remap(&mut active_remaps, &[Key::KEY_RIGHTMETA], Key::KEY_J, 1),
+            Some(vec![(Key::KEY_RIGHTMETA, 0), (Key::KEY_LEFTCTRL, 1), (Key::KEY_N, 1)])
+        );
+        // Super was let go before j, so it is not pressed again
+        assert_eq!(
+            remap(&mut active_remaps, &[], Key::KEY_J, 0),
+            Some(vec![(Key::KEY_N, 0), (Key::KEY_LEFTCTRL, 0)])
+        );
     }
+}
 
-    #[test]
-    fn test_curly_brace_less_keysyms() -> std::io::Result<()> {
-        let contents = "
-super + {a, b}
-    {firefox, brave, chrome}";
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
-                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
-            ],
+mod test_tap_hold {
+    use crate::config::{parse_contents, parse_tap_holds, HoldAction, TapHold};
+    use crate::taphold::{Output, TapHolds};
+    use evdev::{EventType, InputEvent, Key};
+    use std::path::{Path, PathBuf};
+    use tokio::time::Duration;
+
+    fn tap_holds() -> Vec<TapHold> {
+        parse_tap_holds(
+            PathBuf::new(),
+            "
+taphold capslock escape ctrl
+taphold space space @enter Nav permissive-hold
+taphold a a Shift_L hold-on-other-key-press # home row shift",
         )
+        .unwrap()
     }
 
-    #[test]
-    fn test_range_syntax() -> std::io::Result<()> {
-        let contents = "
-super + {1-9,0}
-    bspc desktop -f '{1-9,0}'";
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(
-                    evdev::Key::KEY_1,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '1'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_2,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '2'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_3,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '3'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_4,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '4'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_5,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '5'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_6,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '6'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_7,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '7'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_8,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '8'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_9,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '9'".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_0,
-                    vec![Modifier::Super],
-                    "bspc desktop -f '0'".to_string(),
-                ),
-            ],
-        )
+    // Runs key events through the tap-hold keys and describes what the daemon is told to do
+    fn run(state: &mut TapHolds, events: &[(Key, i32)]) -> Vec<String> {
+        let mut outputs = Vec::new();
+        for (key, value) in events {
+            let event = InputEvent::new(EventType::KEY, key.code(), *value);
+            outputs.extend(state.on_event(
+                &tap_holds(),
+                Duration::from_millis(200),
+                Path::new("/dev/input/event0"),
+                event,
+            ));
+        }
+        describe(outputs)
+    }
+
+    fn describe(outputs: Vec<(PathBuf, Output)>) -> Vec<String> {
+        outputs
+            .into_iter()
+            .map(|(_, output)| match output {
+                Output::Key(event) => format!("{:?} {}", Key::new(event.code()), event.value()),
+                Output::EnterMode(mode) => format!("enter {}", mode),
+                Output::LeaveMode(mode) => format!("leave {}", mode),
+            })
+            .collect()
     }
 
     #[test]
-    fn test_range_syntax_ascii_character() -> std::io::Result<()> {
-        let contents = "
-super + {a-c}
-    {firefox, brave, chrome}";
-        eval_config_test(
-            contents,
+    fn test_parse_tap_holds() {
+        let mut space =
+            TapHold::new(Key::KEY_SPACE, Key::KEY_SPACE, HoldAction::Mode("Nav".to_string()));
+        space.permissive_hold = true;
+        let mut a = TapHold::new(Key::KEY_A, Key::KEY_A, HoldAction::Key(Key::KEY_LEFTSHIFT));
+        a.hold_on_other_key_press = true;
+        assert_eq!(
+            tap_holds(),
             vec![
-                Hotkey::new(evdev::Key::KEY_A, vec![Modifier::Super], "firefox".to_string()),
-                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "brave".to_string()),
-                Hotkey::new(evdev::Key::KEY_C, vec![Modifier::Super], "chrome".to_string()),
-            ],
-        )
+                TapHold::new(Key::KEY_CAPSLOCK, Key::KEY_ESC, HoldAction::Key(Key::KEY_LEFTCTRL)),
+                space,
+                a,
+            ]
+        );
     }
 
     #[test]
-    fn test_range_syntax_not_ascii() -> std::io::Result<()> {
-        let contents = "
-super + {a-是}
-    {firefox, brave}
-    ";
-        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 2))
+    fn test_invalid_tap_holds() {
+        for line in
+            ["taphold capslock escape", "taphold space space @enter", "taphold a b c sticky"]
+        {
+            assert!(parse_tap_holds(PathBuf::new(), line).is_err(), "{} should not parse", line);
+        }
+        let contents = "taphold capslock escape ctrl\nsuper + a\n    notify-send a";
+        assert_eq!(parse_contents(PathBuf::new(), contents.to_string()).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_range_syntax_invalid_range() -> std::io::Result<()> {
-        let contents = "
-super + {bc-ad}
-    {firefox, brave}
-    ";
-        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 2))
+    fn test_tap() {
+        let mut state = TapHolds::new();
+        assert_eq!(
+            run(
+                &mut state,
+                &[(Key::KEY_CAPSLOCK, 1), (Key::KEY_CAPSLOCK, 2), (Key::KEY_CAPSLOCK, 0)]
+            ),
+            vec!["KEY_ESC 1", "KEY_ESC 0"]
+        );
+        assert_eq!(run(&mut state, &[(Key::KEY_B, 1)]), vec!["KEY_B 1"]);
     }
 
     #[test]
-    fn test_ranger_syntax_not_full_range() -> std::io::Result<()> {
-        let contents = "
-super + {a-}
-    {firefox, brave}";
-        eval_invalid_config_test(contents, ParseError::UnknownSymbol(PathBuf::new(), 2))
+    fn test_hold_after_tapping_term() {
+        let mut state = TapHolds::new();
+        assert!(run(&mut state, &[(Key::KEY_CAPSLOCK, 1), (Key::KEY_C, 1)]).is_empty());
+        assert!(state.deadline().is_some());
+        assert_eq!(describe(state.on_timeout()), vec!["KEY_LEFTCTRL 1", "KEY_C 1"]);
+        assert_eq!(state.deadline(), None);
+        assert_eq!(
+            run(&mut state, &[(Key::KEY_C, 0), (Key::KEY_CAPSLOCK, 2), (Key::KEY_CAPSLOCK, 0)]),
+            vec!["KEY_C 0", "KEY_LEFTCTRL 2", "KEY_LEFTCTRL 0"]
+        );
     }
 
     #[test]
-    fn test_none() -> std::io::Result<()> {
-        let contents = "
-super + {_, shift} + b
-    {firefox, brave}";
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(evdev::Key::KEY_B, vec![Modifier::Super], "firefox".to_string()),
-                Hotkey::new(
-                    evdev::Key::KEY_B,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "brave".to_string(),
-                ),
-            ],
-        )
+    fn test_rolling_over_is_a_tap() {
+        // Without permissive hold, another key pressed and released in between is still a tap
+        let mut state = TapHolds::new();
+        assert_eq!(
+            run(
+                &mut state,
+                &[(Key::KEY_CAPSLOCK, 1), (Key::KEY_B, 1), (Key::KEY_B, 0), (Key::KEY_CAPSLOCK, 0)]
+            ),
+            vec!["KEY_ESC 1", "KEY_ESC 0", "KEY_B 1", "KEY_B 0"]
+        );
     }
 
     #[test]
-    fn test_multiple_ranges() -> std::io::Result<()> {
-        let contents = "
-super + {shift,alt} + {c,d}
-    {librewolf, firefox} {--sync, --help}
-            ";
+    fn test_permissive_hold() {
+        let mut state = TapHolds::new();
+        assert_eq!(
+            run(&mut state, &[(Key::KEY_SPACE, 1), (Key::KEY_J, 1), (Key::KEY_J, 0)]),
+            vec!["enter Nav", "KEY_J 1", "KEY_J 0"]
+        );
+        assert_eq!(run(&mut state, &[(Key::KEY_SPACE, 2), (Key::KEY_SPACE, 0)]), vec!["leave Nav"]);
 
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(
-                    evdev::Key::KEY_C,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "librewolf --sync".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_D,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "librewolf --help".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_C,
-                    vec![Modifier::Super, Modifier::Alt],
-                    "firefox --sync".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_D,
-                    vec![Modifier::Super, Modifier::Alt],
-                    "firefox --help".to_string(),
-                ),
-            ],
-        )
+        // A key that was already down when space was pressed does not make it a hold
+        assert!(run(&mut state, &[(Key::KEY_K, 1), (Key::KEY_SPACE, 1), (Key::KEY_K, 0)])
+            .contains(&"KEY_K 1".to_string()));
+        assert_eq!(
+            run(&mut state, &[(Key::KEY_SPACE, 0)]),
+            vec!["KEY_SPACE 1", "KEY_SPACE 0", "KEY_K 0"]
+        );
     }
 
     #[test]
-    fn test_multiple_ranges_numbers() -> std::io::Result<()> {
-        let contents = "
-{control,super} + {1-3}
-    {notify-send, echo} {hello,how,are}
-            ";
+    fn test_hold_on_other_key_press() {
+        let mut state = TapHolds::new();
+        assert_eq!(
+            run(&mut state, &[(Key::KEY_A, 1), (Key::KEY_S, 1)]),
+            vec!["KEY_LEFTSHIFT 1", "KEY_S 1"]
+        );
+        assert_eq!(
+            run(&mut state, &[(Key::KEY_S, 0), (Key::KEY_A, 0)]),
+            vec!["KEY_S 0", "KEY_LEFTSHIFT 0"]
+        );
+    }
+    #[test]
+    fn test_release_holds() {
+        // Pausing ends every hold, so letting go of the key later is not a stale release
+        let mut state = TapHolds::new();
+        assert!(run(&mut state, &[(Key::KEY_CAPSLOCK, 1)]).is_empty());
+        assert_eq!(describe(state.on_timeout()), vec!["KEY_LEFTCTRL 1"]);
+        assert_eq!(describe(state.release_holds(None)), vec!["KEY_LEFTCTRL 0"]);
+        assert!(run(&mut state, &[(Key::KEY_CAPSLOCK, 1)]).is_empty());
+        assert!(state.deadline().is_some());
+
+        // Unplugging only ends the holds of that keyboard
+        let mut state = TapHolds::new();
+        assert!(run(&mut state, &[(Key::KEY_SPACE, 1)]).is_empty());
+        assert_eq!(describe(state.on_timeout()), vec!["enter Nav"]);
+        assert!(state.release_holds(Some(Path::new("/dev/input/event1"))).is_empty());
+        assert_eq!(
+            describe(state.release_holds(Some(Path::new("/dev/input/event0")))),
+            vec!["leave Nav"]
+        );
+    }
+}
 
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(
-                    evdev::Key::KEY_1,
-                    vec![Modifier::Control],
-                    "notify-send hello".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_2,
-                    vec![Modifier::Control],
-                    "notify-send how".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_3,
-                    vec![Modifier::Control],
-                    "notify-send are".to_string(),
-                ),
-                Hotkey::new(evdev::Key::KEY_1, vec![Modifier::Super], "echo hello".to_string()),
-                Hotkey::new(evdev::Key::KEY_2, vec![Modifier::Super], "echo how".to_string()),
-                Hotkey::new(evdev::Key::KEY_3, vec![Modifier::Super], "echo are".to_string()),
-            ],
-        )
+mod test_pointer {
+    use crate::config::{
+        is_pointer_key, parse_contents, Hotkey, KeyBinding, Modifier, Prefix, SCROLL_DOWN,
+        SCROLL_UP,
+    };
+    use crate::pointer::{filter_frame, scroll_key, Binding, Buttons};
+    use evdev::{EventType, InputEvent, Key, RelativeAxisType};
+    use std::path::PathBuf;
+
+    fn button(key: Key, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY, key.code(), value)
     }
 
-    #[test]
-    fn test_bspwm_multiple_curly_brace() -> std::io::Result<()> {
-        let contents = "
-super + {_,shift + }{h,j,k,l}
-	bspc node -{f,s} {west,south,north,east}";
+    fn rel(axis: RelativeAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::RELATIVE, axis.0, value)
+    }
 
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(
-                    evdev::Key::KEY_H,
-                    vec![Modifier::Super],
-                    "bspc node -f west".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_J,
-                    vec![Modifier::Super],
-                    "bspc node -f south".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_K,
-                    vec![Modifier::Super],
-                    "bspc node -f north".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_L,
-                    vec![Modifier::Super],
-                    "bspc node -f east".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_H,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "bspc node -s west".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_J,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "bspc node -s south".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_K,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "bspc node -s north".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_L,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "bspc node -s east".to_string(),
-                ),
-            ],
-        )
+    fn codes(events: &[InputEvent]) -> Vec<(u16, i32)> {
+        events.iter().map(|event| (event.code(), event.value())).collect()
+    }
+
+    // button1 is swallowed, button3 is sent through and scrolling up is swallowed
+    fn binding(key: Key) -> Binding {
+        match key {
+            Key::BTN_LEFT | SCROLL_UP => Binding::Swallow,
+            Key::BTN_RIGHT => Binding::Send,
+            _ => Binding::Unbound,
+        }
     }
 
     #[test]
-    fn test_longer_multiple_curly_brace() -> std::io::Result<()> {
+    fn test_parse_buttons() {
         let contents = "
-super + {_, ctrl +} {_, shift +} {1-2}
-    riverctl {set, toggle}-{focused, view}-tags {1-2}";
-        eval_config_test(
-            contents,
+super + button1
+    notify-send left
+super + {button4, button5}
+    pamixer {-i, -d} 5
+~button3
+    notify-send right
+button9
+    notify-send forward";
+        assert_eq!(
+            parse_contents(PathBuf::new(), contents.to_string()).unwrap(),
             vec![
-                Hotkey::new(
-                    evdev::Key::KEY_1,
-                    vec![Modifier::Super],
-                    "riverctl set-focused-tags 1".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_2,
-                    vec![Modifier::Super],
-                    "riverctl set-focused-tags 2".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_1,
-                    vec![Modifier::Super, Modifier::Control],
-                    "riverctl toggle-focused-tags 1".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_2,
-                    vec![Modifier::Super, Modifier::Control],
-                    "riverctl toggle-focused-tags 2".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_1,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "riverctl set-view-tags 1".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_2,
-                    vec![Modifier::Super, Modifier::Shift],
-                    "riverctl set-view-tags 2".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_1,
-                    vec![Modifier::Super, Modifier::Control, Modifier::Shift],
-                    "riverctl toggle-view-tags 1".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_2,
-                    vec![Modifier::Super, Modifier::Control, Modifier::Shift],
-                    "riverctl toggle-view-tags 2".to_string(),
+                Hotkey::new(Key::BTN_LEFT, vec![Modifier::Super], "notify-send left".to_string()),
+                Hotkey::new(SCROLL_UP, vec![Modifier::Super], "pamixer -i 5".to_string()),
+                Hotkey::new(SCROLL_DOWN, vec![Modifier::Super], "pamixer -d 5".to_string()),
+                Hotkey::from_keybinding(
+                    KeyBinding::new(Key::BTN_RIGHT, vec![]).send(),
+                    "notify-send right".to_string()
                 ),
-            ],
-        )
+                Hotkey::new(Key::BTN_EXTRA, vec![], "notify-send forward".to_string()),
+            ]
+        );
     }
 
     #[test]
-    fn test_period_binding() -> std::io::Result<()> {
-        let contents = "
-super + {comma, period}
-	riverctl focus-output {previous, next}";
+    fn test_display_buttons() {
+        assert_eq!(
+            KeyBinding::new(SCROLL_UP, vec![Modifier::Super]).to_string(),
+            "super + button4"
+        );
+        assert_eq!(KeyBinding::new(Key::BTN_SIDE, vec![]).to_string(), "button8");
+    }
 
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(
-                    evdev::Key::KEY_COMMA,
-                    vec![Modifier::Super],
-                    "riverctl focus-output previous".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_DOT,
-                    vec![Modifier::Super],
-                    "riverctl focus-output next".to_string(),
-                ),
-            ],
-        )
+    #[test]
+    fn test_is_pointer_key() {
+        assert!(is_pointer_key(Key::BTN_LEFT));
+        assert!(is_pointer_key(Key::BTN_EXTRA));
+        assert!(is_pointer_key(SCROLL_DOWN));
+        assert!(!is_pointer_key(Key::KEY_A));
+        assert!(!is_pointer_key(Key::BTN_SOUTH));
     }
 
     #[test]
-    fn test_period_escape_binding() -> std::io::Result<()> {
-        let contents = "
-super + {\\,, .}
-	riverctl focus-output {previous, next}";
+    fn test_scroll_key() {
+        assert_eq!(scroll_key(RelativeAxisType::REL_WHEEL, 1), Some(SCROLL_UP));
+        assert_eq!(scroll_key(RelativeAxisType::REL_WHEEL_HI_RES, -120), Some(SCROLL_DOWN));
+        assert_eq!(scroll_key(RelativeAxisType::REL_HWHEEL, -1), Some(crate::config::SCROLL_LEFT));
+        assert_eq!(scroll_key(RelativeAxisType::REL_X, 5), None);
+        assert_eq!(scroll_key(RelativeAxisType::REL_WHEEL, 0), None);
+    }
 
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(
-                    evdev::Key::KEY_COMMA,
-                    vec![Modifier::Super],
-                    "riverctl focus-output previous".to_string(),
-                ),
-                Hotkey::new(
-                    evdev::Key::KEY_DOT,
-                    vec![Modifier::Super],
-                    "riverctl focus-output next".to_string(),
-                ),
-            ],
-        )
+    #[test]
+    fn test_unbound_frame_is_replayed() {
+        let mut buttons = Buttons::new();
+        let frame = vec![
+            rel(RelativeAxisType::REL_X, 3),
+            rel(RelativeAxisType::REL_Y, -2),
+            button(Key::BTN_MIDDLE, 1),
+        ];
+        let (events, triggered) = filter_frame(frame.clone(), &mut buttons, binding);
+        assert_eq!(codes(&events), codes(&frame));
+        assert!(triggered.is_empty());
     }
 
     #[test]
-    fn test_prefix() -> std::io::Result<()> {
-        let contents = "
-super + @1
-    1
-super + ~2
-    2
-super + ~@3
-    3
-super + @~4
-    4";
+    fn test_swallowed_button() {
+        let mut buttons = Buttons::new();
+        let (events, triggered) = filter_frame(
+            vec![button(Key::BTN_LEFT, 1), rel(RelativeAxisType::REL_X, 1)],
+            &mut buttons,
+            binding,
+        );
+        assert_eq!(codes(&events), vec![(RelativeAxisType::REL_X.0, 1)]);
+        assert_eq!(triggered, vec![(Key::BTN_LEFT, 1)]);
+
+        // The release is swallowed too, even once the button is no longer bound
+        let (events, triggered) =
+            filter_frame(vec![button(Key::BTN_LEFT, 0)], &mut buttons, |_| Binding::Unbound);
+        assert!(events.is_empty());
+        assert_eq!(triggered, vec![(Key::BTN_LEFT, 0)]);
+    }
 
-        eval_config_test(
-            contents,
-            vec![
-                Hotkey::new(evdev::Key::KEY_1, vec![Modifier::Super], "1".to_string()).on_release(),
-                Hotkey::new(evdev::Key::KEY_2, vec![Modifier::Super], "2".to_string()).send(),
-                Hotkey::new(evdev::Key::KEY_3, vec![Modifier::Super], "3".to_string())
-                    .on_release()
-                    .send(),
-                Hotkey::new(evdev::Key::KEY_4, vec![Modifier::Super], "4".to_string())
-                    .on_release()
-                    .send(),
-            ],
-        )
+    #[test]
+    fn test_sent_button() {
+        let mut buttons = Buttons::new();
+        let (events, triggered) =
+            filter_frame(vec![button(Key::BTN_RIGHT, 1)], &mut buttons, binding);
+        assert_eq!(codes(&events), vec![(Key::BTN_RIGHT.code(), 1)]);
+        assert_eq!(triggered, vec![(Key::BTN_RIGHT, 1)]);
+
+        let (events, triggered) =
+            filter_frame(vec![button(Key::BTN_RIGHT, 0)], &mut buttons, binding);
+        assert_eq!(codes(&events), vec![(Key::BTN_RIGHT.code(), 0)]);
+        assert_eq!(triggered, vec![(Key::BTN_RIGHT, 0)]);
+    }
+
+    #[test]
+    fn test_scroll_steps() {
+        let mut buttons = Buttons::new();
+        // Scrolling up is bound, so both wheel events are dropped and one step is triggered
+        let (events, triggered) = filter_frame(
+            vec![rel(RelativeAxisType::REL_WHEEL, 1), rel(RelativeAxisType::REL_WHEEL_HI_RES, 120)],
+            &mut buttons,
+            binding,
+        );
+        assert!(events.is_empty());
+        assert_eq!(triggered, vec![(SCROLL_UP, 1), (SCROLL_UP, 0)]);
+
+        // A partial step of a high resolution wheel triggers nothing
+        let (events, triggered) =
+            filter_frame(vec![rel(RelativeAxisType::REL_WHEEL_HI_RES, 30)], &mut buttons, binding);
+        assert!(events.is_empty());
+        assert!(triggered.is_empty());
+
+        // Scrolling down is not bound
+        let (events, triggered) =
+            filter_frame(vec![rel(RelativeAxisType::REL_WHEEL, -1)], &mut buttons, binding);
+        assert_eq!(codes(&events), vec![(RelativeAxisType::REL_WHEEL.0, -1)]);
+        assert!(triggered.is_empty());
     }
 }
 
-mod test_config_display {
-    use crate::config::{Error, ParseError};
-    use std::io;
-    use std::path::PathBuf;
+mod test_watcher {
+    use crate::watcher::ConfigWatcher;
+    use std::{fs, path::PathBuf};
+    use tokio::time::{timeout, Duration};
 
-    #[test]
-    fn test_display_io_error() {
-        let error = Error::Io(io::Error::from(io::ErrorKind::UnexpectedEof));
+    // A directory with a config file, removed with everything in it when dropped
+    struct TestDir {
+        path: PathBuf,
+    }
 
-        if !format!("{}", error).contains("unexpected end of file") {
-            panic!("Error message was '{}", error);
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = PathBuf::from(format!("/tmp/swhkd-test-{}", name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("swhkdrc"), "super + a\n    a\n").unwrap();
+            TestDir { path }
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
         }
     }
 
+    async fn changed(watcher: &ConfigWatcher) -> Option<Vec<PathBuf>> {
+        timeout(Duration::from_millis(500), watcher.changed()).await.ok().map(Result::unwrap)
+    }
+
+    #[tokio::test]
+    async fn test_watch_write() {
+        let dir = TestDir::new("watcher-write");
+        let config = dir.path.join("swhkdrc");
+        let mut watcher = ConfigWatcher::new().unwrap();
+        watcher.watch(std::slice::from_ref(&config), &[]);
+
+        fs::write(&config, "super + b\n    b\n").unwrap();
+        assert_eq!(changed(&watcher).await, Some(vec![config]));
+    }
+
+    #[tokio::test]
+    async fn test_watch_rename() {
+        let dir = TestDir::new("watcher-rename");
+        let config = dir.path.join("swhkdrc");
+        let mut watcher = ConfigWatcher::new().unwrap();
+        watcher.watch(std::slice::from_ref(&config), &[]);
+
+        // Like an editor that saves through a temporary file
+        let temporary = dir.path.join("swhkdrc.tmp");
+        fs::write(&temporary, "super + b\n    b\n").unwrap();
+        fs::rename(&temporary, &config).unwrap();
+        assert_eq!(changed(&watcher).await, Some(vec![config.clone()]));
+
+        // The watch survives the rename
+        fs::write(&config, "super + c\n    c\n").unwrap();
+        assert_eq!(changed(&watcher).await, Some(vec![config]));
+    }
+
+    #[tokio::test]
+    async fn test_ignore_other_files() {
+        let dir = TestDir::new("watcher-other");
+        let config = dir.path.join("swhkdrc");
+        let mut watcher = ConfigWatcher::new().unwrap();
+        watcher.watch(&[config], &[]);
+
+        fs::write(dir.path.join("notes"), "not a config").unwrap();
+        assert_eq!(changed(&watcher).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_watch_replaces_paths() {
+        let dir = TestDir::new("watcher-replace");
+        let config = dir.path.join("swhkdrc");
+        let include = dir.path.join("include");
+        fs::write(&include, "super + b\n    b\n").unwrap();
+        let mut watcher = ConfigWatcher::new().unwrap();
+        watcher.watch(&[config.clone(), include.clone()], &[]);
+        watcher.watch(&[config], &[]);
+
+        fs::write(&include, "super + c\n    c\n").unwrap();
+        assert_eq!(changed(&watcher).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_watch_include_dir() {
+        let dir = TestDir::new("watcher-include-dir");
+        let config = dir.path.join("swhkdrc");
+        let include_dir = dir.path.join("swhkdrc.d");
+        fs::create_dir(&include_dir).unwrap();
+        let mut watcher = ConfigWatcher::new().unwrap();
+        watcher.watch(&[config], std::slice::from_ref(&include_dir));
+
+        fs::write(include_dir.join(".swap"), "").unwrap();
+        assert_eq!(changed(&watcher).await, None);
+
+        let fragment = include_dir.join("10-work.rc");
+        fs::write(&fragment, "super + b\n    b\n").unwrap();
+        assert_eq!(changed(&watcher).await, Some(vec![fragment]));
+    }
+}
+
+mod test_config_error_command {
+    use crate::shell_quote;
+
     #[test]
-    fn test_display_unknown_symbol_error() {
-        let error = Error::InvalidConfig(ParseError::UnknownSymbol(PathBuf::new(), 10));
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("Error in /etc/swhkd/swhkdrc"), "'Error in /etc/swhkd/swhkdrc'");
+        assert_eq!(shell_quote("don't $(rm -rf /)"), "'don'\\''t $(rm -rf /)'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
+
+mod test_devices {
+    use crate::config::{parse_remaps, Modifier};
+    use crate::devices::{seat_of, DeviceFilter, DeviceInfo, Matcher, Seat};
+    use crate::remap::remap_event;
+    use crate::{remove_keyboard_state, sharing_state, HeldKeys, KeyboardState};
+    use evdev::{AttributeSet, Key};
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+
+    fn yubikey() -> DeviceInfo {
+        DeviceInfo {
+            path: PathBuf::from("/dev/input/event7"),
+            name: String::from("Yubico YubiKey OTP+FIDO+CCID"),
+            vendor: 0x1050,
+            product: 0x0407,
+            bus: 0x03,
+            phys: String::from("usb-0000:00:14.0-2/input0"),
+            links: vec![PathBuf::from("/dev/input/by-id/usb-Yubico_YubiKey-event-kbd")],
+        }
+    }
 
+    fn laptop_keyboard() -> DeviceInfo {
+        DeviceInfo {
+            path: PathBuf::from("/dev/input/event3"),
+            name: String::from("AT Translated Set 2 keyboard"),
+            vendor: 0x0001,
+            product: 0x0001,
+            bus: 0x11,
+            phys: String::from("isa0060/serio0/input0"),
+            links: vec![PathBuf::from("/dev/input/by-path/platform-i8042-serio-0-event-kbd")],
+        }
+    }
+
+    #[test]
+    fn test_parse_matcher() {
+        assert_eq!("Macro Pad".parse(), Ok(Matcher::Name(String::from("Macro Pad"))));
+        assert_eq!("name:\"*Keyboard*\"".parse(), Ok(Matcher::Name(String::from("*Keyboard*"))));
         assert_eq!(
-            format!("{}", error),
-            "Error parsing config file \"\". Unknown symbol at line 10."
+            "id:1050:0407".parse(),
+            Ok(Matcher::Id { vendor: 0x1050, product: Some(0x0407) })
+        );
+        assert_eq!("id:0x1050".parse(), Ok(Matcher::Id { vendor: 0x1050, product: None }));
+        assert_eq!("bus:USB".parse(), Ok(Matcher::Bus(0x03)));
+        assert_eq!("bus:1f".parse(), Ok(Matcher::Bus(0x1f)));
+        assert_eq!("phys:usb-*".parse(), Ok(Matcher::Phys(String::from("usb-*"))));
+        assert_eq!(
+            "id:yubikey".parse::<Matcher>(),
+            Err(String::from("invalid device matcher `id:yubikey`"))
         );
+        assert!("bus:firewire".parse::<Matcher>().is_err());
+        assert!("path:".parse::<Matcher>().is_err());
     }
 
     #[test]
-    fn test_display_invalid_modifier_error() {
-        let error = Error::InvalidConfig(ParseError::InvalidModifier(PathBuf::new(), 25));
+    fn test_display_matcher() {
+        for matcher in ["name:*Keyboard*", "id:1050:0407", "id:1050:*", "bus:bluetooth", "bus:1f"] {
+            assert_eq!(matcher.parse::<Matcher>().unwrap().to_string(), matcher);
+        }
+    }
+
+    #[test]
+    fn test_match_device() {
+        let matches = |matcher: &str| matcher.parse::<Matcher>().unwrap().matches(&yubikey());
+        assert!(matches("*yubikey*"));
+        assert!(!matches("YubiKey"));
+        assert!(matches("id:1050:*"));
+        assert!(!matches("id:1050:0010"));
+        assert!(matches("bus:usb"));
+        assert!(matches("path:/dev/input/event7"));
+        assert!(matches("path:/dev/input/by-id/*Yubico*"));
+        assert!(matches("phys:usb-0000:00:14.0-2/*"));
+        assert!(!matches("phys:isa*"));
+    }
 
+    #[test]
+    fn test_device_filter() {
+        let mut filter = DeviceFilter::default();
+        assert_eq!(filter.check(&yubikey()), Ok(String::from("no allow-device rules")));
+
+        filter.deny.push("id:1050".parse().unwrap());
+        assert_eq!(filter.check(&yubikey()), Err(String::from("matches deny-device id:1050:*")));
+        assert!(filter.check(&laptop_keyboard()).is_ok());
+
+        filter.extend(DeviceFilter {
+            allow: vec!["bus:i8042".parse().unwrap(), "name:Yubico*".parse().unwrap()],
+            deny: Vec::new(),
+        });
         assert_eq!(
-            format!("{}", error),
-            "Error parsing config file \"\". Invalid modifier at line 25."
+            filter.check(&laptop_keyboard()),
+            Ok(String::from("matches allow-device bus:i8042"))
         );
+        // Deny rules win over allow rules
+        assert!(filter.check(&yubikey()).is_err());
+        let other = DeviceInfo { name: String::from("Macro Pad"), ..DeviceInfo::default() };
+        assert_eq!(filter.check(&other), Err(String::from("matches no allow-device rule")));
     }
 
     #[test]
-    fn test_invalid_keysm_error() {
-        let error = Error::InvalidConfig(ParseError::InvalidKeysym(PathBuf::new(), 7));
+    fn test_seat_of() {
+        let seats = vec![
+            Seat { name: String::from("desk"), devices: vec!["bus:usb".parse().unwrap()] },
+            Seat { name: String::from("default"), devices: Vec::new() },
+        ];
+        assert_eq!(seat_of(&seats, &yubikey()), Some("desk"));
+        assert_eq!(seat_of(&seats, &laptop_keyboard()), Some("default"));
+        assert_eq!(seat_of(&seats[..1], &laptop_keyboard()), None);
+    }
+
+    #[test]
+    fn test_shared_state() {
+        let usb_keyboard = |path: &str| DeviceInfo {
+            path: PathBuf::from(path),
+            bus: 0x03,
+            ..DeviceInfo::default()
+        };
+        let mut keyboard_states = HashMap::new();
+        for device in [
+            usb_keyboard("/dev/input/event5"),
+            usb_keyboard("/dev/input/event6"),
+            laptop_keyboard(),
+        ] {
+            keyboard_states.insert(device.path.clone(), KeyboardState::new(device));
+        }
+        let state = keyboard_states.get_mut(Path::new("/dev/input/event5")).unwrap();
+        state.state_modifiers.insert(Modifier::Super);
+        state.state_modifier_keys.insert(Key::KEY_LEFTMETA);
+        keyboard_states
+            .get_mut(Path::new("/dev/input/event3"))
+            .unwrap()
+            .state_keysyms
+            .insert(Key::KEY_A);
+
+        // Without seats, every keyboard has its own state
+        let held =
+            HeldKeys::new(sharing_state(&keyboard_states, &[], Path::new("/dev/input/event6")));
+        assert!(held.modifiers.is_empty());
+
+        let seats =
+            vec![Seat { name: String::from("desk"), devices: vec!["bus:usb".parse().unwrap()] }];
+        let held =
+            HeldKeys::new(sharing_state(&keyboard_states, &seats, Path::new("/dev/input/event6")));
+        assert_eq!(held.modifiers.into_iter().collect::<Vec<_>>(), vec![Modifier::Super]);
+        assert!(held.modifier_keys.contains(Key::KEY_LEFTMETA));
+        assert!(!held.keysyms.contains(Key::KEY_A));
+
+        let held =
+            HeldKeys::new(sharing_state(&keyboard_states, &seats, Path::new("/dev/input/event3")));
+        assert!(held.modifiers.is_empty());
+        assert!(held.keysyms.contains(Key::KEY_A));
+    }
 
+    #[test]
+    fn test_release_keys_of_removed_keyboard() {
+        let remaps = parse_remaps(PathBuf::new(), "remap ctrl + h -> backspace").unwrap();
+        let mut keyboard_states = HashMap::new();
+        for device in [yubikey(), laptop_keyboard()] {
+            keyboard_states.insert(device.path.clone(), KeyboardState::new(device));
+        }
+        let state = keyboard_states.get_mut(Path::new("/dev/input/event3")).unwrap();
+        state.state_modifiers.insert(Modifier::Control);
+        state.state_modifier_keys.insert(Key::KEY_LEFTCTRL);
+        state.state_keysyms.insert(Key::KEY_A);
+        state.state_keysyms.insert(Key::KEY_B);
+        let mut modifier_keys = AttributeSet::new();
+        modifier_keys.insert(Key::KEY_LEFTCTRL);
+        remap_event(
+            &remaps,
+            &mut state.active_remaps,
+            &HashSet::from([Modifier::Control]),
+            &modifier_keys,
+            Key::KEY_H,
+            1,
+        );
+        // Still held on the other keyboard, so not released
+        keyboard_states
+            .get_mut(Path::new("/dev/input/event7"))
+            .unwrap()
+            .state_keysyms
+            .insert(Key::KEY_B);
+
+        let released: Vec<(Key, i32)> =
+            remove_keyboard_state(&mut keyboard_states, Path::new("/dev/input/event3"))
+                .iter()
+                .map(|event| (Key::new(event.code()), event.value()))
+                .collect();
         assert_eq!(
-            format!("{}", error),
-            "Error parsing config file \"\". Invalid keysym at line 7."
+            released,
+            vec![(Key::KEY_LEFTCTRL, 0), (Key::KEY_A, 0), (Key::KEY_BACKSPACE, 0)]
+        );
+        assert!(!keyboard_states.contains_key(Path::new("/dev/input/event3")));
+        assert!(
+            remove_keyboard_state(&mut keyboard_states, Path::new("/dev/input/event3")).is_empty()
         );
     }
 }
+
+mod test_stale_events {
+    use crate::is_stale;
+    use evdev::{EventType, InputEvent, Key};
+    use nix::libc;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    fn key_event(time: SystemTime) -> InputEvent {
+        let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        InputEvent::from(libc::input_event {
+            time: libc::timeval {
+                tv_sec: since_epoch.as_secs() as libc::time_t,
+                tv_usec: since_epoch.subsec_micros() as libc::suseconds_t,
+            },
+            type_: EventType::KEY.0,
+            code: Key::KEY_A.code(),
+            value: 1,
+        })
+    }
+
+    #[test]
+    fn test_drop_events_queued_while_stopped() {
+        let resumed_at = SystemTime::now();
+        let keyboard = Path::new("/dev/input/event3");
+        let mut stale_before = HashMap::from([(PathBuf::from(keyboard), resumed_at)]);
+
+        let typed_while_stopped = key_event(resumed_at - Duration::from_secs(5));
+        assert!(is_stale(&mut stale_before, keyboard, &typed_while_stopped));
+        assert!(is_stale(&mut stale_before, keyboard, &typed_while_stopped));
+
+        // Other devices weren't marked
+        assert!(!is_stale(&mut stale_before, Path::new("/dev/input/event4"), &typed_while_stopped));
+
+        let typed_after = key_event(resumed_at + Duration::from_secs(1));
+        assert!(!is_stale(&mut stale_before, keyboard, &typed_after));
+        // Once caught up, the device is live again even if the clock went back
+        assert!(stale_before.is_empty());
+        assert!(!is_stale(&mut stale_before, keyboard, &typed_while_stopped));
+    }
+}
+
+mod test_chain_timeout {
+    use crate::config::{parse_contents, Value};
+    use crate::{apply_mode_action, chain_deadline};
+    use std::path::PathBuf;
+    use tokio::time::{Duration, Instant};
+
+    #[test]
+    fn test_release_triggered_chain_prefix() {
+        let hotkeys =
+            parse_contents(PathBuf::new(), "super + @a ; b\n    firefox".to_string()).unwrap();
+        let prefix = &hotkeys[0];
+        assert!(prefix.is_on_release());
+
+        let timeout = Some(Duration::from_secs(2));
+        let mut mode_stack = Vec::new();
+        assert!(chain_deadline(timeout, &mode_stack).is_none());
+
+        // Run on the release, the prefix enters the chain, whose timer then restarts
+        let before = Instant::now();
+        apply_mode_action(prefix.mode_action().unwrap(), &mut mode_stack);
+        let deadline = chain_deadline(timeout, &mode_stack).unwrap();
+        assert!(deadline >= before + Duration::from_secs(2));
+        assert!(chain_deadline(None, &mode_stack).is_none());
+    }
+}
@@ -1,8 +1,11 @@
+use crate::config::KeyStroke;
 use evdev::{
     uinput::{VirtualDevice, VirtualDeviceBuilder},
-    AttributeSet, Key,
+    AbsInfo, AttributeSet, AttributeSetRef, Device, EventType, InputEvent, Key, UinputAbsSetup,
 };
 
+pub const POINTER_DEVICE_NAME: &str = "swhkd virtual pointer";
+
 pub fn create_uinput_device() -> Result<VirtualDevice, Box<dyn std::error::Error>> {
     let mut keys = AttributeSet::<Key>::new();
     for key in get_all_keys() {
@@ -15,6 +18,81 @@ pub fn create_uinput_device() -> Result<VirtualDevice, Box<dyn std::error::Error
         .unwrap();
     Ok(device)
 }
+
+// Releases every key held on the virtual keyboard. The kernel drops releases of keys that are
+// not down, so only the held ones reach applications.
+pub fn release_all_keys(device: &mut VirtualDevice) -> std::io::Result<()> {
+    let releases: Vec<InputEvent> =
+        get_all_keys().iter().map(|key| InputEvent::new(EventType::KEY, key.code(), 0)).collect();
+    device.emit(&releases)
+}
+
+// A virtual device with the buttons, axes and properties of a grabbed pointer device, so its
+// events can be replayed unchanged. The input id is kept for libinput's device quirks.
+pub fn create_pointer_device(device: &Device) -> std::io::Result<VirtualDevice> {
+    let mut builder = VirtualDeviceBuilder::new()?
+        .name(POINTER_DEVICE_NAME)
+        .input_id(device.input_id())
+        .with_properties(device.properties())?;
+    if let Some(keys) = device.supported_keys() {
+        builder = builder.with_keys(keys)?;
+    }
+    if let Some(axes) = device.supported_relative_axes() {
+        builder = builder.with_relative_axes(axes)?;
+    }
+    if let Some(axes) = device.supported_absolute_axes() {
+        let abs_state = device.get_abs_state()?;
+        for axis in axes.iter() {
+            let info = abs_state[axis.0 as usize];
+            let info = AbsInfo::new(
+                info.value,
+                info.minimum,
+                info.maximum,
+                info.fuzz,
+                info.flat,
+                info.resolution,
+            );
+            builder = builder.with_absolute_axis(&UinputAbsSetup::new(axis, info))?;
+        }
+    }
+    if let Some(misc) = device.misc_properties() {
+        builder = builder.with_msc(misc)?;
+    }
+    builder.build()
+}
+
+// Types the keystrokes on the virtual keyboard. Every emit ends with its own SYN_REPORT, so each
+// key change is a separate frame. Modifier keys held on the virtual keyboard, like the ones of
+// the hotkey that triggered this, are released first and pressed again after.
+pub fn emit_keystrokes(
+    device: &mut VirtualDevice,
+    keystrokes: &[KeyStroke],
+    held_keys: &AttributeSetRef<Key>,
+) -> std::io::Result<()> {
+    let key_events = |keys: &[Key], value: i32| -> Vec<InputEvent> {
+        keys.iter().map(|key| InputEvent::new(EventType::KEY, key.code(), value)).collect()
+    };
+    let held_keys: Vec<Key> = held_keys.iter().collect();
+
+    if !held_keys.is_empty() {
+        device.emit(&key_events(&held_keys, 0))?;
+    }
+    for keystroke in keystrokes {
+        if !keystroke.modifier_keys.is_empty() {
+            device.emit(&key_events(&keystroke.modifier_keys, 1))?;
+        }
+        device.emit(&key_events(&[keystroke.key], 1))?;
+        device.emit(&key_events(&[keystroke.key], 0))?;
+        if !keystroke.modifier_keys.is_empty() {
+            device.emit(&key_events(&keystroke.modifier_keys, 0))?;
+        }
+    }
+    if !held_keys.is_empty() {
+        device.emit(&key_events(&held_keys, 1))?;
+    }
+    Ok(())
+}
+
 pub fn get_all_keys() -> Vec<evdev::Key> {
     return vec![
         evdev::Key::KEY_RESERVED,
@@ -0,0 +1,126 @@
+use nix::{
+    errno::Errno,
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::io::unix::AsyncFd;
+
+// Watches the config files for changes. Editors often save by writing a new file and renaming it
+// over the old one, which a watch on the file itself would not survive, so the directories holding
+// the files are watched instead. Symlinked files are watched at both ends.
+pub struct ConfigWatcher {
+    inotify: AsyncFd<Inotify>,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    files: HashSet<PathBuf>,
+    // Directories whose every file counts, for directory and glob includes
+    include_dirs: HashSet<PathBuf>,
+}
+
+fn watch_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_DELETE
+}
+
+// The file as its directory and file name, so it can be compared with inotify events
+fn split_path(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let name = path.file_name()?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Some((dir.clone(), dir.join(name)))
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        Ok(ConfigWatcher {
+            inotify: AsyncFd::new(inotify)?,
+            dirs: HashMap::new(),
+            files: HashSet::new(),
+            include_dirs: HashSet::new(),
+        })
+    }
+
+    // Watches these files, and any file in these directories, instead of the ones watched before
+    pub fn watch(&mut self, paths: &[PathBuf], include_dirs: &[PathBuf]) {
+        let mut files = HashSet::new();
+        let mut dirs: HashSet<PathBuf> = include_dirs.iter().cloned().collect();
+        for path in paths {
+            for path in [Some(path.clone()), fs::canonicalize(path).ok()].into_iter().flatten() {
+                if let Some((dir, file)) = split_path(&path) {
+                    dirs.insert(dir);
+                    files.insert(file);
+                }
+            }
+        }
+
+        let inotify = *self.inotify.get_ref();
+        self.dirs.retain(|wd, dir| {
+            if dirs.contains(dir) {
+                return true;
+            }
+            let _ = inotify.rm_watch(*wd);
+            false
+        });
+        for dir in dirs {
+            if self.dirs.values().any(|watched| *watched == dir) {
+                continue;
+            }
+            match inotify.add_watch(&dir, watch_flags()) {
+                Ok(wd) => {
+                    log::debug!("Watching {:?} for config changes", dir);
+                    self.dirs.insert(wd, dir);
+                }
+                Err(e) => log::warn!("Unable to watch {:?} for config changes: {}", dir, e),
+            }
+        }
+        self.files = files;
+        self.include_dirs = include_dirs.iter().cloned().collect();
+    }
+
+    // Waits until one of the watched files changes, and returns the files that did. An error
+    // means the watcher can't be waited on anymore.
+    pub async fn changed(&self) -> std::io::Result<Vec<PathBuf>> {
+        loop {
+            let mut guard = self.inotify.readable().await?;
+            let events = match guard.get_inner().read_events() {
+                Ok(events) => events,
+                Err(Errno::EAGAIN) => {
+                    guard.clear_ready();
+                    continue;
+                }
+                Err(e) => {
+                    log::error!("Failed to read config changes: {}", e);
+                    guard.clear_ready();
+                    continue;
+                }
+            };
+
+            let mut changed: Vec<PathBuf> = Vec::new();
+            for event in events {
+                let (dir, name) = match (self.dirs.get(&event.wd), event.name) {
+                    (Some(dir), Some(name)) => (dir, name),
+                    _ => continue,
+                };
+                let file = dir.join(&name);
+                // Editors keep their swap and backup files hidden
+                let included =
+                    self.include_dirs.contains(dir) && !name.to_string_lossy().starts_with('.');
+                if (included || self.files.contains(&file)) && !changed.contains(&file) {
+                    changed.push(file);
+                }
+            }
+            if !changed.is_empty() {
+                return Ok(changed);
+            }
+        }
+    }
+}